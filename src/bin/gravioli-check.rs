@@ -0,0 +1,45 @@
+//! Looks for design problems in every level (or the ones named on the command
+//! line, by name or level file path): a rocket spawning inside a planet, a goal
+//! covered by a planet or out of reach, orbits running into other bodies,
+//! missing planet images and a rocket left with no way to move.
+//!
+//! Usage: gravioli-check [--solve] [--evaluations N] [--seed N] [--attempts N] [level name or file...]
+//!
+//! `--solve` also searches each level for a winning run, as `gravioli-solve`
+//! does, and reports the ones it couldn't win.
+
+use std::process::ExitCode;
+use gravioli::check;
+use gravioli::cli::{self, Args};
+use gravioli::state::LevelState;
+
+fn main() -> ExitCode {
+    let args = Args::from_env(&["--solve"]);
+    let levels = match cli::levels(&args.levels) {
+        Ok(levels) => levels,
+        Err(error) => {
+            eprintln!("{}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut broken = 0;
+    for level in levels {
+        let name = level.name;
+        let mut problems = check::problems(&level);
+        // a level that's already broken isn't worth searching
+        if args.has("--solve") && problems.is_empty() && !cli::solve(&LevelState::new(level), &args.options, args.attempts).1.won {
+            problems.push("no winning run found".to_string());
+        }
+        if problems.is_empty() {
+            println!("{}: ok", name);
+        } else {
+            println!("{}:", name);
+            for problem in &problems {
+                println!("    {}", problem);
+            }
+            broken += 1;
+        }
+    }
+    if broken > 0 { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
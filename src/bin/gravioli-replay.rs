@@ -0,0 +1,49 @@
+//! Compares exported daily challenge replays, such as ones shared by teammates.
+//!
+//! Usage: gravioli-replay FILE...
+//!
+//! Prints each run's result and burn time, then for every pair of runs of the
+//! same day when their paths first part ways.
+
+use std::process::ExitCode;
+use gravioli::daily::{self, Replay};
+
+/// Pixels apart two runs have to be to count as taking different paths.
+const APART: f32 = 50.0;
+
+fn main() -> ExitCode {
+    let paths: Vec<String> = std::env::args().skip(1).collect();
+    if paths.is_empty() {
+        eprintln!("Usage: gravioli-replay FILE...");
+        return ExitCode::FAILURE;
+    }
+
+    let mut replays = Vec::new();
+    for path in &paths {
+        let replay = std::fs::read_to_string(path).map_err(|error| error.to_string()).and_then(|text| Replay::parse(&text));
+        match replay {
+            Ok(replay) => replays.push((path, replay)),
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    for (path, replay) in &replays {
+        let result = replay.result.as_ref().map_or_else(|| "unfinished".to_string(), |result| result.description());
+        println!("{} ({}): {}, {:.1}s of burn", path, replay.date, result, replay.burn_time());
+    }
+    for (i, (a_path, a)) in replays.iter().enumerate() {
+        for (b_path, b) in replays.iter().skip(i + 1) {
+            if a.date != b.date {
+                continue;
+            }
+            match daily::divergence(a, b, APART) {
+                Some(time) => println!("{} and {} part ways at {:.1}s", a_path, b_path, time),
+                None => println!("{} and {} fly the same path", a_path, b_path),
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
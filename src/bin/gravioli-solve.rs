@@ -0,0 +1,45 @@
+//! Searches every level (or the ones named on the command line, by name or level
+//! file path) for a winning run and prints par times, without opening a window.
+//! The times go into each level's `par`, which the medals are measured against.
+//!
+//! Usage: gravioli-solve [--time] [--evaluations N] [--seed N] [--attempts N] [level name or file...]
+//!
+//! `--time` looks for the fastest run instead of the one using the least fuel.
+//! A level that isn't solved is searched again with the next seed, up to
+//! `--attempts` times.
+
+use std::process::ExitCode;
+use gravioli::cli::{self, Args};
+use gravioli::solver::Objective;
+use gravioli::state::LevelState;
+
+fn main() -> ExitCode {
+    let mut args = Args::from_env(&["--time"]);
+    if args.has("--time") {
+        args.options.objective = Objective::Time;
+    }
+    let levels = match cli::levels(&args.levels) {
+        Ok(levels) => levels,
+        Err(error) => {
+            eprintln!("{}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut unsolved = 0;
+    for level in levels {
+        let (name, listed) = (level.name, level.par);
+        let (plan, outcome) = cli::solve(&LevelState::new(level), &args.options, args.attempts);
+        if outcome.won {
+            let listed = listed.map_or_else(String::new, |par| format!(" (listed {:.1}s)", par));
+            println!("{}: par {:.1}s{}, {:.1}s of burn", name, outcome.time, listed, outcome.burn);
+        } else {
+            println!("{}: no solution found, closest miss {:.0}", name, outcome.miss);
+            unsolved += 1;
+        }
+        for burn in &plan.burns {
+            println!("    at {:.2}s burn {:.2}s towards {:.0}°", burn.start, burn.duration, burn.heading);
+        }
+    }
+    if unsolved > 0 { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
@@ -0,0 +1,235 @@
+use macroquad::prelude::*;
+use crate::state::{LevelState, WinCondition};
+
+const MAX_ZOOM: f32 = 4.0;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CameraMode {
+    /// Keep the rocket in the middle of the view
+    Follow,
+    /// Zoom and pan so the rocket and the goal both fit on screen
+    Frame,
+    /// Stay wherever the player dragged the view to
+    Free,
+}
+
+/// The view onto the world. `zoom` is screen pixels per world pixel.
+#[derive(Clone)]
+pub struct Camera {
+    pub mode: CameraMode,
+    pub center: Vec2,
+    pub zoom: f32,
+    pub world: Vec2,
+    pub drag_from: Option<Vec2>, // last mouse position while dragging
+}
+
+impl Camera {
+    /// Starts following the rocket, zoomed to fit the whole world if that's not too far out.
+    pub fn new(world: Vec2, screen: Vec2) -> Self {
+        let zoom = fit_zoom(world, screen).max(0.5);
+        let mut camera = Self { mode: CameraMode::Follow, center: world / 2.0, zoom, world, drag_from: None };
+        camera.clamp(screen);
+        camera
+    }
+
+    pub fn next_mode(&mut self) {
+        self.mode = match self.mode {
+            CameraMode::Follow => CameraMode::Frame,
+            CameraMode::Frame => CameraMode::Free,
+            CameraMode::Free => CameraMode::Follow,
+        };
+    }
+
+    /// Eases the view towards what the current mode wants to show.
+    pub fn update(&mut self, level: &LevelState, dt: f32, screen: Vec2) {
+        let ease = 1.0 - (-5.0 * dt).exp();
+        let rocket = vec2(level.rocket.x, level.rocket.y);
+        match self.mode {
+            CameraMode::Follow => self.center += (rocket - self.center) * ease,
+            CameraMode::Frame => {
+                let (x, y, radius) = match level.level.win_condition {
+                    WinCondition::Circle { x, y, radius, .. } | WinCondition::CircleAnySpeed { x, y, radius } => (x, y, radius),
+                };
+                let min = rocket.min(vec2(x - radius, y - radius));
+                let max = rocket.max(vec2(x + radius, y + radius));
+                let (center, zoom) = frame(min, max, screen);
+                self.center += (center - self.center) * ease;
+                self.zoom += (zoom - self.zoom) * ease;
+            }
+            CameraMode::Free => {}
+        }
+        self.clamp(screen);
+    }
+
+    /// Zooms by `factor`, keeping the world point under `anchor` (in screen pixels) in place.
+    pub fn zoom_at(&mut self, factor: f32, anchor: Vec2, screen: Vec2) {
+        let before = self.screen_to_world(anchor, screen);
+        self.zoom *= factor;
+        self.clamp(screen);
+        let after = self.screen_to_world(anchor, screen);
+        self.center += before - after;
+        if self.mode == CameraMode::Frame {
+            self.mode = CameraMode::Follow;
+        }
+        self.clamp(screen);
+    }
+
+    /// Moves the view along with a mouse drag of `delta` screen pixels.
+    pub fn pan(&mut self, delta: Vec2, screen: Vec2) {
+        self.center -= delta / self.zoom;
+        self.mode = CameraMode::Free;
+        self.clamp(screen);
+    }
+
+    pub fn screen_to_world(&self, point: Vec2, screen: Vec2) -> Vec2 {
+        self.center + (point - screen / 2.0) / self.zoom
+    }
+
+    pub fn world_to_screen(&self, point: Vec2, screen: Vec2) -> Vec2 {
+        (point - self.center) * self.zoom + screen / 2.0
+    }
+
+    pub fn to_camera2d(&self, screen: Vec2) -> Camera2D {
+        Camera2D {
+            target: self.center,
+            // negative y keeps the y axis pointing down like screen coordinates
+            zoom: vec2(2.0 * self.zoom / screen.x, -2.0 * self.zoom / screen.y),
+            ..Default::default()
+        }
+    }
+
+    /// Keeps the zoom in range and the view inside the world, centering the world
+    /// on any axis where it's smaller than the view.
+    fn clamp(&mut self, screen: Vec2) {
+        self.zoom = self.zoom.clamp(fit_zoom(self.world, screen).min(MAX_ZOOM), MAX_ZOOM);
+        let half_view = screen / (2.0 * self.zoom);
+        let axis = |center: f32, half: f32, size: f32| {
+            if 2.0 * half >= size { size / 2.0 } else { center.clamp(half, size - half) }
+        };
+        self.center = vec2(
+            axis(self.center.x, half_view.x, self.world.x),
+            axis(self.center.y, half_view.y, self.world.y),
+        );
+    }
+}
+
+/// The zoom at which the whole world just fits on screen.
+pub fn fit_zoom(world: Vec2, screen: Vec2) -> f32 {
+    (screen.x / world.x).min(screen.y / world.y)
+}
+
+/// Where to put an edge-of-screen marker for something at `point` (in screen
+/// pixels): the spot where the line from the screen center towards it crosses a
+/// border inset by `margin`. `None` if the point is on screen.
+pub fn edge_indicator(point: Vec2, screen: Vec2, margin: f32) -> Option<Vec2> {
+    let on_screen = point.x >= 0.0 && point.y >= 0.0 && point.x <= screen.x && point.y <= screen.y;
+    if on_screen {
+        return None;
+    }
+    let center = screen / 2.0;
+    let direction = point - center;
+    let half = center - vec2(margin, margin);
+    // shrink the direction until it touches the nearer of the two borders
+    let scale = (half.x / direction.x.abs()).min(half.y / direction.y.abs());
+    Some(center + direction * scale)
+}
+
+/// Center and zoom that fit the box from `min` to `max` on screen with a margin.
+fn frame(min: Vec2, max: Vec2, screen: Vec2) -> (Vec2, f32) {
+    let margin = 1.3;
+    let size = ((max - min) * margin).max(vec2(1.0, 1.0));
+    ((min + max) / 2.0, fit_zoom(size, screen))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen() -> Vec2 {
+        vec2(800.0, 600.0)
+    }
+
+    #[test]
+    fn new_camera_fits_small_world() {
+        let camera = Camera::new(vec2(1600.0, 900.0), screen());
+        assert_eq!(camera.zoom, 0.5);
+        assert_eq!(camera.center, vec2(800.0, 450.0));
+    }
+
+    #[test]
+    fn screen_to_world_scales_around_center() {
+        let mut camera = Camera::new(vec2(4000.0, 4000.0), screen());
+        camera.center = vec2(1000.0, 2000.0);
+        camera.zoom = 2.0;
+        assert_eq!(camera.screen_to_world(screen() / 2.0, screen()), camera.center);
+        assert_eq!(camera.screen_to_world(vec2(0.0, 0.0), screen()), vec2(800.0, 1850.0));
+    }
+
+    #[test]
+    fn view_stays_inside_world() {
+        let mut camera = Camera::new(vec2(4000.0, 4000.0), screen());
+        camera.zoom = 1.0;
+        camera.pan(vec2(10000.0, 10000.0), screen());
+        assert_eq!(camera.center, vec2(400.0, 300.0));
+        assert!(camera.mode == CameraMode::Free);
+    }
+
+    #[test]
+    fn world_smaller_than_view_is_centered() {
+        let mut camera = Camera::new(vec2(400.0, 300.0), screen());
+        camera.pan(vec2(50.0, 50.0), screen());
+        assert_eq!(camera.center, vec2(200.0, 150.0));
+    }
+
+    #[test]
+    fn zoom_is_limited() {
+        let mut camera = Camera::new(vec2(4000.0, 4000.0), screen());
+        camera.zoom_at(100.0, screen() / 2.0, screen());
+        assert_eq!(camera.zoom, MAX_ZOOM);
+        camera.zoom_at(0.0001, screen() / 2.0, screen());
+        assert_eq!(camera.zoom, fit_zoom(camera.world, screen()));
+    }
+
+    #[test]
+    fn zoom_keeps_anchor_in_place() {
+        let mut camera = Camera::new(vec2(4000.0, 4000.0), screen());
+        camera.zoom = 1.0;
+        camera.center = vec2(2000.0, 2000.0);
+        let anchor = vec2(600.0, 100.0);
+        let before = camera.screen_to_world(anchor, screen());
+        camera.zoom_at(1.5, anchor, screen());
+        assert!((camera.screen_to_world(anchor, screen()) - before).length() < 0.001);
+    }
+
+    #[test]
+    fn world_and_screen_round_trip() {
+        let mut camera = Camera::new(vec2(4000.0, 4000.0), screen());
+        camera.center = vec2(1000.0, 2000.0);
+        camera.zoom = 2.0;
+        let point = vec2(123.0, 456.0);
+        let back = camera.screen_to_world(camera.world_to_screen(point, screen()), screen());
+        assert!((back - point).length() < 0.001);
+    }
+
+    #[test]
+    fn no_indicator_on_screen() {
+        assert_eq!(edge_indicator(vec2(10.0, 590.0), screen(), 20.0), None);
+    }
+
+    #[test]
+    fn indicator_sits_on_nearest_border() {
+        assert_eq!(edge_indicator(vec2(2000.0, 300.0), screen(), 20.0), Some(vec2(780.0, 300.0)));
+        assert_eq!(edge_indicator(vec2(400.0, -500.0), screen(), 20.0), Some(vec2(400.0, 20.0)));
+        // straight down and to the left hits the bottom border first
+        let corner = edge_indicator(vec2(-100.0, 2000.0), screen(), 20.0).unwrap();
+        assert!((corner.y - 580.0).abs() < 0.001);
+        assert!(corner.x > 20.0 && corner.x < 400.0);
+    }
+
+    #[test]
+    fn frame_fits_box_with_margin() {
+        let (center, zoom) = frame(vec2(0.0, 0.0), vec2(1000.0, 100.0), screen());
+        assert_eq!(center, vec2(500.0, 50.0));
+        assert!(zoom < 0.8 && zoom > 0.5);
+    }
+}
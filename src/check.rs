@@ -0,0 +1,279 @@
+use macroquad::prelude::*;
+use crate::images::PLANET_IMAGES;
+use crate::state::{Bounds, Level, PlanetDef, WinCondition};
+
+/// Design problems in a level that would make it broken or unfair, described
+/// for a level designer. Empty when the level looks fine.
+pub fn problems(level: &Level) -> Vec<String> {
+    let mut problems = Vec::new();
+    let rocket = &level.initial_rocket;
+    let area = play_area(level);
+
+    for (i, def) in level.planets.iter().enumerate() {
+        let planet = def.planet_at(0.0);
+        let dist = vec2(rocket.x - planet.x, rocket.y - planet.y).length();
+        // a rocket landed on a planet sits exactly on its surface
+        if dist < planet.radius - 0.5 {
+            problems.push(format!("the rocket starts inside planet {}", i));
+        }
+        if def.image >= PLANET_IMAGES {
+            problems.push(format!("planet {} uses image {}, but only 0-{} exist", i, def.image, PLANET_IMAGES - 1));
+        }
+    }
+    if !area.contains(vec2(rocket.x, rocket.y)) {
+        problems.push("the rocket starts outside the play area".to_string());
+    }
+
+    let (goal, goal_radius) = match level.win_condition {
+        WinCondition::Circle { x, y, radius, .. } | WinCondition::CircleAnySpeed { x, y, radius } => (vec2(x, y), radius),
+    };
+    for (i, def) in level.planets.iter().enumerate() {
+        if closest_approach(path(def), (goal, 0.0)) < def.radius + goal_radius {
+            let verb = if def.orbit.is_some() { "passes through" } else { "overlaps" };
+            problems.push(format!("planet {} {} the goal", i, verb));
+        }
+    }
+    let nearest = vec2(goal.x.clamp(area.x, area.right()), goal.y.clamp(area.y, area.bottom()));
+    if nearest.distance(goal) >= goal_radius {
+        problems.push("the goal is outside the play area".to_string());
+    }
+
+    for (i, a) in level.planets.iter().enumerate() {
+        for (j, b) in level.planets.iter().enumerate().skip(i + 1) {
+            if closest_distance(a, b) >= a.radius + b.radius {
+                continue;
+            }
+            let problem = match (&a.orbit, &b.orbit) {
+                (None, None) => format!("planets {} and {} overlap", i, j),
+                (Some(_), None) => format!("the orbit of planet {} crosses planet {}", i, j),
+                (None, Some(_)) => format!("the orbit of planet {} crosses planet {}", j, i),
+                (Some(_), Some(_)) => format!("the orbits of planets {} and {} cross", i, j),
+            };
+            problems.push(problem);
+        }
+    }
+
+    let fuel: f32 = rocket.stages.iter().map(|stage| stage.fuel_mass).sum();
+    if fuel <= 0.0 && rocket.speed_x == 0.0 && rocket.speed_y == 0.0 {
+        problems.push("the rocket has no fuel and starts at rest".to_string());
+    }
+    // both go into the stage's mass flow and delta-v as divisors
+    for (i, stage) in rocket.stages.iter().enumerate() {
+        if stage.dry_mass <= 0.0 {
+            problems.push(format!("stage {} has no dry mass", i));
+        }
+        if stage.isp <= 0.0 {
+            problems.push(format!("stage {} has no Isp", i));
+        }
+    }
+    for depot in &level.depots {
+        if depot.planet >= level.planets.len() {
+            problems.push(format!("a depot is on planet {}, which doesn't exist", depot.planet));
+        }
+    }
+    if let Some(star) = level.star.filter(|star| *star >= level.planets.len()) {
+        problems.push(format!("the star is planet {}, which doesn't exist", star));
+    }
+    if let Some(par) = level.par.filter(|par| *par <= 0.0) {
+        problems.push(format!("the par time is {}s, no run can beat it", par));
+    }
+    problems
+}
+
+/// Where the rocket can fly: the bounds' rectangle, or the whole world when the
+/// level is open.
+fn play_area(level: &Level) -> Rect {
+    match level.bounds {
+        Bounds::Open => Rect::new(0.0, 0.0, level.world.x, level.world.y),
+        Bounds::Wrap(rect) | Bounds::Wall { rect, .. } | Bounds::Lose(rect) => rect,
+    }
+}
+
+/// The circle a planet's center moves along, a single point for a static planet.
+pub fn path(def: &PlanetDef) -> (Vec2, f32) {
+    (vec2(def.center_x, def.center_y), def.orbit.as_ref().map_or(0.0, |orbit| orbit.radius))
+}
+
+/// How close the centers of two planets can get. Planets going round the same
+/// center at the same rate keep their distance, like a binary star; otherwise
+/// any two points on their paths could line up.
+pub fn closest_distance(a: &PlanetDef, b: &PlanetDef) -> f32 {
+    if let (Some(a_orbit), Some(b_orbit)) = (&a.orbit, &b.orbit) {
+        let same_center = path(a).0.distance(path(b).0) < 0.01;
+        if same_center && a_orbit.speed == b_orbit.speed {
+            let (a, b) = (a.planet_at(0.0), b.planet_at(0.0));
+            return vec2(a.x - b.x, a.y - b.y).length();
+        }
+    }
+    closest_approach(path(a), path(b))
+}
+
+/// Closest distance between any point on one circle and any point on the other,
+/// each given as center and radius.
+pub fn closest_approach((a, a_radius): (Vec2, f32), (b, b_radius): (Vec2, f32)) -> f32 {
+    let d = a.distance(b);
+    if d > a_radius + b_radius {
+        d - a_radius - b_radius
+    } else if d < (a_radius - b_radius).abs() {
+        (a_radius - b_radius).abs() - d
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{FuelDepot, Orbit, Rocket, RotationMode, Stage};
+
+    fn make_planet(x: f32, y: f32, radius: f32) -> PlanetDef {
+        PlanetDef { center_x: x, center_y: y, radius, density: 1.0, image: 0, spin: 0.0, orbit: None, atmosphere: None }
+    }
+
+    fn make_level(planets: Vec<PlanetDef>) -> Level {
+        Level {
+            name: "Test",
+            planets,
+            initial_rocket: Rocket {
+                x: 100.0,
+                y: 100.0,
+                speed_x: 0.0,
+                speed_y: 0.0,
+                orientation: 0.0,
+                landed: false,
+                throttle: 0.0,
+                stages: vec![Stage { dry_mass: 0.5, fuel_mass: 0.5, thrust: 10.0, isp: 40.0 }],
+                heat: 0.0,
+                angular_velocity: 0.0,
+                rcs_fuel: 0.0,
+                strafe_x: 0.0,
+                strafe_y: 0.0,
+            },
+            win_condition: WinCondition::CircleAnySpeed { x: 900.0, y: 500.0, radius: 50.0 },
+            rotation: RotationMode::Instant,
+            canisters: vec![],
+            depots: vec![],
+            hazards: vec![],
+            bounds: Bounds::Open,
+            world: vec2(1000.0, 600.0),
+            star: None,
+            assists: true,
+            seed: None,
+            par: None,
+        }
+    }
+
+    fn orbiting(planet: PlanetDef, radius: f32) -> PlanetDef {
+        PlanetDef { orbit: Some(Orbit { radius, speed: 1.0, initial_angle: 0.0 }), ..planet }
+    }
+
+    #[test]
+    fn shipped_levels_have_no_problems() {
+        for level in Level::all() {
+            assert_eq!(problems(&level), Vec::<String>::new(), "{}", level.name);
+        }
+    }
+
+    #[test]
+    fn clean_level_has_no_problems() {
+        assert!(problems(&make_level(vec![make_planet(500.0, 300.0, 40.0)])).is_empty());
+    }
+
+    #[test]
+    fn rocket_inside_planet() {
+        let level = make_level(vec![make_planet(110.0, 100.0, 30.0)]);
+        assert_eq!(problems(&level), vec!["the rocket starts inside planet 0"]);
+        // landed on the surface is fine
+        let level = make_level(vec![make_planet(130.0, 100.0, 30.0)]);
+        assert!(problems(&level).is_empty());
+    }
+
+    #[test]
+    fn goal_overlapping_planets() {
+        let level = make_level(vec![make_planet(900.0, 420.0, 40.0)]);
+        assert_eq!(problems(&level), vec!["planet 0 overlaps the goal"]);
+        let level = make_level(vec![orbiting(make_planet(900.0, 300.0, 20.0), 200.0)]);
+        assert_eq!(problems(&level), vec!["planet 0 passes through the goal"]);
+    }
+
+    #[test]
+    fn goal_outside_play_area() {
+        let mut level = make_level(vec![]);
+        level.bounds = Bounds::Lose(Rect::new(0.0, 0.0, 800.0, 600.0));
+        assert_eq!(problems(&level), vec!["the goal is outside the play area"]);
+        // reaching in over the edge is enough
+        level.bounds = Bounds::Lose(Rect::new(0.0, 0.0, 880.0, 600.0));
+        assert!(problems(&level).is_empty());
+    }
+
+    #[test]
+    fn crossing_orbits() {
+        let sun = make_planet(500.0, 300.0, 50.0);
+        let level = make_level(vec![sun.clone(), orbiting(make_planet(500.0, 300.0, 10.0), 55.0)]);
+        assert_eq!(problems(&level), vec!["the orbit of planet 1 crosses planet 0"]);
+        let inner = orbiting(make_planet(500.0, 300.0, 10.0), 100.0);
+        let outer = orbiting(make_planet(500.0, 300.0, 10.0), 115.0);
+        let level = make_level(vec![sun.clone(), inner.clone(), outer]);
+        assert_eq!(problems(&level), vec!["the orbits of planets 1 and 2 cross"]);
+        let far = orbiting(make_planet(500.0, 300.0, 10.0), 150.0);
+        assert!(problems(&make_level(vec![sun, inner, far])).is_empty());
+    }
+
+    #[test]
+    fn binary_pair_never_meets() {
+        let a = orbiting(make_planet(500.0, 300.0, 30.0), 100.0);
+        let b = PlanetDef { orbit: Some(Orbit { radius: 100.0, speed: 1.0, initial_angle: std::f32::consts::PI }), ..a.clone() };
+        assert!(problems(&make_level(vec![a.clone(), b])).is_empty());
+        let close = PlanetDef { orbit: Some(Orbit { radius: 100.0, speed: 1.0, initial_angle: 0.3 }), ..a.clone() };
+        assert_eq!(problems(&make_level(vec![a, close])), vec!["the orbits of planets 0 and 1 cross"]);
+    }
+
+    #[test]
+    fn static_planets_overlap() {
+        let level = make_level(vec![make_planet(500.0, 300.0, 50.0), make_planet(560.0, 300.0, 20.0)]);
+        assert_eq!(problems(&level), vec!["planets 0 and 1 overlap"]);
+    }
+
+    #[test]
+    fn bad_indices() {
+        let mut level = make_level(vec![PlanetDef { image: 10, ..make_planet(500.0, 300.0, 40.0) }]);
+        level.depots.push(FuelDepot { planet: 3, angle: 0.0, rate: 0.1 });
+        level.star = Some(1);
+        assert_eq!(problems(&level), vec![
+            "planet 0 uses image 10, but only 0-9 exist",
+            "a depot is on planet 3, which doesn't exist",
+            "the star is planet 1, which doesn't exist",
+        ]);
+    }
+
+    #[test]
+    fn stranded_rocket() {
+        let mut level = make_level(vec![]);
+        level.initial_rocket.stages[0].fuel_mass = 0.0;
+        assert_eq!(problems(&level), vec!["the rocket has no fuel and starts at rest"]);
+        level.initial_rocket.speed_x = 10.0;
+        assert!(problems(&level).is_empty());
+    }
+
+    #[test]
+    fn massless_or_isp_free_stage() {
+        let mut level = make_level(vec![]);
+        level.initial_rocket.stages[0].dry_mass = 0.0;
+        level.initial_rocket.stages[0].isp = -5.0;
+        assert_eq!(problems(&level), vec!["stage 0 has no dry mass", "stage 0 has no Isp"]);
+    }
+
+    #[test]
+    fn par_time_must_be_positive() {
+        let mut level = make_level(vec![]);
+        level.par = Some(0.0);
+        assert_eq!(problems(&level), vec!["the par time is 0s, no run can beat it"]);
+    }
+
+    #[test]
+    fn shipped_levels_have_par_times() {
+        for level in Level::all() {
+            assert!(level.par.is_some(), "{}", level.name);
+        }
+    }
+}
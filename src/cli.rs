@@ -0,0 +1,123 @@
+//! Command line handling shared by `gravioli-check` and `gravioli-solve`.
+
+use crate::level_file;
+use crate::solver::{self, Options, Outcome, Plan};
+use crate::state::{Level, LevelState};
+
+/// What a level tool was asked to do.
+pub struct Args {
+    pub options: Options,
+    pub attempts: u64,         // solver runs per level, each with the next seed
+    pub switches: Vec<String>, // the tool's own flags that were given, like `--solve`
+    pub levels: Vec<String>,   // level names or level file paths, empty for every level
+}
+
+impl Args {
+    /// Reads the program's arguments, `switches` being the flags without a value
+    /// this tool knows on top of the solver settings. Exits on a bad command line.
+    pub fn from_env(switches: &[&str]) -> Self {
+        match Self::parse(std::env::args().skip(1), switches) {
+            Ok(args) => args,
+            Err(error) => {
+                eprintln!("{}", error);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    pub fn parse(args: impl IntoIterator<Item = String>, switches: &[&str]) -> Result<Self, String> {
+        let mut parsed = Self { options: Options::default(), attempts: 3, switches: Vec::new(), levels: Vec::new() };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--evaluations" => match parse_number(args.next(), "--evaluations")? {
+                    0 => return Err("--evaluations needs at least one run".to_string()),
+                    evaluations => parsed.options.evaluations = evaluations,
+                },
+                "--seed" => parsed.options.seed = parse_number(args.next(), "--seed")?,
+                "--attempts" => parsed.attempts = parse_number(args.next(), "--attempts")?,
+                switch if switches.contains(&switch) => parsed.switches.push(arg),
+                flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag)),
+                _ => parsed.levels.push(arg),
+            }
+        }
+        Ok(parsed)
+    }
+
+    pub fn has(&self, switch: &str) -> bool {
+        self.switches.iter().any(|given| given == switch)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: Option<String>, flag: &str) -> Result<T, String> {
+    value.and_then(|v| v.parse().ok()).ok_or_else(|| format!("{} needs a number", flag))
+}
+
+/// The levels asked for: the built-in level for each name and the level in each
+/// file, or every built-in level when none were named.
+pub fn levels(names: &[String]) -> Result<Vec<Level>, String> {
+    if names.is_empty() {
+        return Ok(Level::all());
+    }
+    let all = Level::all();
+    names
+        .iter()
+        .map(|name| {
+            if std::path::Path::new(name).is_file() {
+                return level_file::load(name).map_err(|error| format!("{}: {}", name, error));
+            }
+            all.iter().find(|level| level.name == name).cloned().ok_or_else(|| format!("No level called {}", name))
+        })
+        .collect()
+}
+
+/// Searches for a win with up to `attempts` seeds, starting from the one in
+/// `options`, returning the first winning run or the last miss.
+pub fn solve(start: &LevelState, options: &Options, attempts: u64) -> (Plan, Outcome) {
+    let mut attempt = 0;
+    loop {
+        let seed = options.seed + attempt;
+        let (plan, outcome) = solver::solve(start, Options { seed, ..options.clone() });
+        attempt += 1;
+        if outcome.won || attempt >= attempts {
+            return (plan, outcome);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn reads_solver_settings_switches_and_levels() {
+        let parsed = Args::parse(args("--solve --evaluations 500 Slingshot --seed 4 --attempts 1"), &["--solve"]).unwrap();
+        assert!(parsed.has("--solve"));
+        assert!(!parsed.has("--time"));
+        assert_eq!(parsed.options.evaluations, 500);
+        assert_eq!(parsed.options.seed, 4);
+        assert_eq!(parsed.attempts, 1);
+        assert_eq!(parsed.levels, vec!["Slingshot"]);
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert_eq!(Args::parse(args("--seed x"), &[]).err().unwrap(), "--seed needs a number");
+        assert_eq!(Args::parse(args("--attempts"), &[]).err().unwrap(), "--attempts needs a number");
+        assert_eq!(Args::parse(args("--evaluations 0"), &[]).err().unwrap(), "--evaluations needs at least one run");
+        assert_eq!(Args::parse(args("--time"), &["--solve"]).err().unwrap(), "Unknown option --time");
+    }
+
+    #[test]
+    fn finds_levels_by_name_or_file() {
+        assert_eq!(levels(&[]).unwrap().len(), Level::all().len());
+        let names = vec!["Level 1".to_string(), "levels/example.txt".to_string()];
+        let found = levels(&names).unwrap();
+        assert_eq!(found.iter().map(|level| level.name).collect::<Vec<_>>(), vec!["Level 1", "Moon Hop"]);
+        assert_eq!(levels(&["Nowhere".to_string()]).err().unwrap(), "No level called Nowhere");
+    }
+}
@@ -1,44 +1,341 @@
 use macroquad::prelude::*;
-use crate::state::Rocket;
+use crate::physics::{self, PhysicsEvent};
+use crate::solver::{self, Options, Solver};
+use crate::state::{Assist, GameState, Rocket, RotationMode};
 
-pub fn handle_input(rocket: &mut Rocket, dt: f32, show_hud: &mut bool, show_path: &mut bool) -> bool {
+/// `trigger` is where a gamepad's analog trigger was pulled to this frame, if it moved.
+pub fn handle_input(game: &mut GameState, dt: f32, trigger: Option<f32>) -> bool {
     if is_key_pressed(KeyCode::Escape) {
         return true;
     }
-    update_orientation(rocket, dt, is_key_down(KeyCode::A), is_key_down(KeyCode::D));
-    if is_key_pressed(KeyCode::Z) && rocket.fuel > 0.0 {
-        rocket.engine_on = true;
+    // the daily challenge is a single attempt flown without help
+    let daily = game.replay.is_some();
+    if is_key_pressed(KeyCode::R) && !daily {
+        game.restart();
     }
-    if is_key_pressed(KeyCode::X) {
-        rocket.engine_on = false;
+    handle_camera_input(game);
+    if game.crash.is_some() {
+        return false;
+    }
+    if !daily {
+        handle_solver_input(game);
+    }
+    let searching = game.solver.as_ref().is_some_and(|solver| !solver.finished());
+    let taking_over = is_key_down(KeyCode::A) || is_key_down(KeyCode::D) || is_key_pressed(KeyCode::Z) || is_key_pressed(KeyCode::X) || trigger.is_some();
+    if game.autopilot && taking_over {
+        game.autopilot = false;
+    }
+    let solved = game.solver.as_ref().is_some_and(|solver| solver.solution().is_some());
+    if game.autopilot && !searching && !solved {
+        game.autopilot = false;
+    }
+    // the rocket stays as the solver found it until the search is done, and the
+    // autopilot steers in `update`
+    if !game.autopilot && !searching {
+        fly_by_hand(game, dt, trigger);
     }
     if is_key_pressed(KeyCode::H) {
-        *show_hud = !*show_hud;
+        game.show_hud = !game.show_hud;
     }
     if is_key_pressed(KeyCode::P) {
-        *show_path = !*show_path;
+        game.show_path = !game.show_path;
+    }
+    if is_key_pressed(KeyCode::M) {
+        game.show_minimap = !game.show_minimap;
+    }
+    if is_key_pressed(KeyCode::O) {
+        game.show_orbits = !game.show_orbits;
+    }
+    if is_key_pressed(KeyCode::G) {
+        game.show_field = !game.show_field;
+    }
+    if is_key_pressed(KeyCode::L) {
+        game.show_past_trails = !game.show_past_trails;
+    }
+    if is_key_pressed(KeyCode::LeftBracket) {
+        game.path_horizon = (game.path_horizon - 1.0).max(1.0);
+    }
+    if is_key_pressed(KeyCode::RightBracket) {
+        game.path_horizon = (game.path_horizon + 1.0).min(30.0);
+    }
+    if is_key_pressed(KeyCode::Comma) {
+        game.burn_plan = update_burn_plan(game.burn_plan, -1.0);
+    }
+    if is_key_pressed(KeyCode::Period) {
+        game.burn_plan = update_burn_plan(game.burn_plan, 1.0);
     }
     false
 }
 
+/// Turning, RCS, throttle and staging from the keyboard, with any assist holding
+/// the heading. A gamepad trigger sets the throttle outright.
+fn fly_by_hand(game: &mut GameState, dt: f32, trigger: Option<f32>) {
+    let rotate_left = is_key_down(KeyCode::A);
+    let rotate_right = is_key_down(KeyCode::D);
+    handle_assist_input(game, rotate_left || rotate_right);
+    let target = game.assist.and_then(|assist| {
+        let level = &game.level;
+        physics::assist_heading(assist, &level.rocket, &level.current_planets(), &level.level.win_condition)
+    });
+    let rocket = &mut game.level.rocket;
+    match (game.level.level.rotation, target) {
+        (RotationMode::Instant, Some(target)) => hold_orientation(rocket, dt, target),
+        (RotationMode::Instant, None) => update_orientation(rocket, dt, rotate_left, rotate_right),
+        (RotationMode::Momentum, _) => {
+            if is_key_pressed(KeyCode::T) {
+                game.sas = !game.sas;
+            }
+            match target {
+                Some(target) => hold_angular_velocity(rocket, dt, target),
+                None => update_angular_velocity(rocket, dt, rotate_left, rotate_right, game.sas),
+            }
+        }
+    }
+    update_strafe(rocket, is_key_down(KeyCode::Q), is_key_down(KeyCode::E), is_key_down(KeyCode::W), is_key_down(KeyCode::S));
+    if is_key_pressed(KeyCode::Z) && rocket.fuel() > 0.0 {
+        rocket.throttle = 1.0;
+    }
+    if is_key_pressed(KeyCode::X) {
+        rocket.throttle = 0.0;
+    }
+    if rocket.fuel() > 0.0 {
+        if let Some(pulled) = trigger {
+            set_throttle(rocket, pulled);
+        }
+        update_throttle(rocket, dt, is_key_down(KeyCode::LeftShift), is_key_down(KeyCode::LeftControl));
+    }
+    if is_key_pressed(KeyCode::Space) {
+        physics::jettison_stage(&mut game.level);
+    }
+}
+
+/// Simulated runs per hint search. The game stands still while it runs.
+const HINT_EVALUATIONS: usize = 2000;
+
+/// I shows or hides a solution from where the rocket is now, J has the autopilot
+/// fly one. Turning or using the throttle by hand takes back control.
+fn handle_solver_input(game: &mut GameState) {
+    let start_search = |game: &GameState| {
+        let options = Options { evaluations: HINT_EVALUATIONS, ..Default::default() };
+        Some(Solver::new(game.level.clone(), options))
+    };
+    if is_key_pressed(KeyCode::I) {
+        game.autopilot = false;
+        game.solver = if game.solver.is_some() { None } else { start_search(game) };
+    }
+    if is_key_pressed(KeyCode::J) {
+        game.autopilot = !game.autopilot;
+        if game.autopilot && game.solver.is_none() {
+            game.solver = start_search(game);
+        }
+    }
+}
+
+/// Advances the world by a frame. With the autopilot on, the solver's plan is
+/// flown in the solver's own fixed steps, counting from the level time it
+/// searched from, whatever the frame time.
+pub fn update(game: &mut GameState, dt: f32) -> Option<PhysicsEvent> {
+    let solution = game.solver.as_ref().filter(|_| game.autopilot && game.crash.is_none());
+    let Some((plan, start_time)) = solution.and_then(|solver| Some((solver.solution()?.0.clone(), solver.start_time()))) else {
+        game.autopilot_lag = 0.0;
+        return physics::update(game, dt);
+    };
+    game.autopilot_lag += dt;
+    while game.autopilot_lag >= solver::DT {
+        game.autopilot_lag -= solver::DT;
+        let steps = ((game.level.time - start_time) / solver::DT as f64).round();
+        solver::steer(&mut game.level, &plan, steps as f32 * solver::DT, solver::DT);
+        if let Some(event) = physics::update(game, solver::DT) {
+            game.autopilot_lag = 0.0;
+            return Some(event);
+        }
+    }
+    None
+}
+
+/// 1 to 5 pick a direction to hold and pressing the same key again lets go, as
+/// does turning by hand. Levels in hard mode don't allow assists at all.
+fn handle_assist_input(game: &mut GameState, turning: bool) {
+    if !game.level.level.assists {
+        game.assist = None;
+        return;
+    }
+    let keys = [
+        (KeyCode::Key1, Assist::Prograde),
+        (KeyCode::Key2, Assist::Retrograde),
+        (KeyCode::Key3, Assist::RadialIn),
+        (KeyCode::Key4, Assist::RadialOut),
+        (KeyCode::Key5, Assist::Goal),
+    ];
+    for (key, assist) in keys {
+        if is_key_pressed(key) {
+            game.assist = if game.assist == Some(assist) { None } else { Some(assist) };
+        }
+    }
+    if turning {
+        game.assist = None;
+    }
+}
+
+/// C cycles the camera mode, the mouse wheel zooms around the cursor and dragging
+/// with the left button pans.
+fn handle_camera_input(game: &mut GameState) {
+    let camera = &mut game.camera;
+    let screen = vec2(screen_width(), screen_height());
+    if is_key_pressed(KeyCode::C) {
+        camera.next_mode();
+    }
+    let mouse = Vec2::from(mouse_position());
+    let (_, wheel) = mouse_wheel();
+    if wheel != 0.0 {
+        // wheel units differ between platforms, so only the direction counts
+        camera.zoom_at(1.1f32.powf(wheel.signum()), mouse, screen);
+    }
+    if is_mouse_button_down(MouseButton::Left) {
+        if let Some(from) = camera.drag_from {
+            camera.pan(mouse - from, screen);
+        }
+        camera.drag_from = Some(mouse);
+    } else {
+        camera.drag_from = None;
+    }
+}
+
+/// Degrees per second that A/D turn the rocket in instant rotation levels.
+const ROTATION_SPEED: f32 = 360.0 / 4.0;
+
 pub fn update_orientation(rocket: &mut Rocket, dt: f32, rotate_left: bool, rotate_right: bool) {
-    let rotation_speed = 360.0 / 4.0; // degrees per second
     if rotate_right {
-        rocket.orientation += rotation_speed * dt;
+        rocket.orientation += ROTATION_SPEED * dt;
     }
     if rotate_left {
-        rocket.orientation -= rotation_speed * dt;
+        rocket.orientation -= ROTATION_SPEED * dt;
     }
     rocket.orientation = rocket.orientation.rem_euclid(360.0);
 }
 
+/// Degrees per second squared the RCS thrusters change the spin by in momentum levels.
+const ANGULAR_ACCEL: f32 = 120.0;
+
+/// Signed shortest turn in degrees from `from` to `to`, positive clockwise.
+fn angle_between(from: f32, to: f32) -> f32 {
+    (to - from + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// Turns the rocket towards `target` at the same rate as A/D, without overshooting.
+pub fn hold_orientation(rocket: &mut Rocket, dt: f32, target: f32) {
+    let error = angle_between(rocket.orientation, target);
+    let step = ROTATION_SPEED * dt;
+    rocket.orientation = (rocket.orientation + error.clamp(-step, step)).rem_euclid(360.0);
+}
+
+/// Fires the RCS thrusters to spin the rocket towards `target`, slowing down
+/// in time to stop on it.
+pub fn hold_angular_velocity(rocket: &mut Rocket, dt: f32, target: f32) {
+    if rocket.rcs_fuel <= 0.0 {
+        return;
+    }
+    let error = angle_between(rocket.orientation, target);
+    // the fastest spin that can still be braked to a stop within the remaining angle
+    let wanted = error.signum() * (2.0 * ANGULAR_ACCEL * error.abs()).sqrt().min(ROTATION_SPEED);
+    let max_change = ANGULAR_ACCEL * dt;
+    let change = (wanted - rocket.angular_velocity).clamp(-max_change, max_change);
+    rocket.angular_velocity += change;
+    rocket.rcs_fuel = (rocket.rcs_fuel - change.abs() / ANGULAR_ACCEL).max(0.0);
+}
+
+/// Fires the RCS thrusters to change the rocket's spin. With SAS on and no
+/// rotation keys held, the thrusters counter any remaining spin.
+pub fn update_angular_velocity(rocket: &mut Rocket, dt: f32, rotate_left: bool, rotate_right: bool, sas: bool) {
+    if rocket.rcs_fuel <= 0.0 {
+        return;
+    }
+    let max_change = ANGULAR_ACCEL * dt;
+    let change = match (rotate_left, rotate_right) {
+        (false, true) => max_change,
+        (true, false) => -max_change,
+        (false, false) if sas => (-rocket.angular_velocity).clamp(-max_change, max_change),
+        _ => 0.0,
+    };
+    rocket.angular_velocity += change;
+    rocket.rcs_fuel = (rocket.rcs_fuel - change.abs() / ANGULAR_ACCEL).max(0.0);
+}
+
+pub fn update_strafe(rocket: &mut Rocket, left: bool, right: bool, forward: bool, back: bool) {
+    let axis = |negative: bool, positive: bool| match (negative, positive) {
+        (true, false) => -1.0,
+        (false, true) => 1.0,
+        _ => 0.0,
+    };
+    rocket.strafe_x = axis(left, right);
+    rocket.strafe_y = axis(back, forward);
+}
+
+pub fn update_throttle(rocket: &mut Rocket, dt: f32, increase: bool, decrease: bool) {
+    let throttle_speed = 0.5; // full range in two seconds
+    if increase {
+        rocket.throttle += throttle_speed * dt;
+    }
+    if decrease {
+        rocket.throttle -= throttle_speed * dt;
+    }
+    rocket.throttle = rocket.throttle.clamp(0.0, 1.0);
+}
+
+/// Puts the throttle straight at `value`, for analog input.
+pub fn set_throttle(rocket: &mut Rocket, value: f32) {
+    rocket.throttle = value.clamp(0.0, 1.0);
+}
+
+/// Steps the planned burn length by `change` seconds. Past the longest planned
+/// burn comes "until the stage is empty", shown as `None`.
+pub fn update_burn_plan(plan: Option<f32>, change: f32) -> Option<f32> {
+    let longest = 20.0;
+    let seconds = plan.unwrap_or(longest + 1.0) + change;
+    if seconds > longest { None } else { Some(seconds.max(1.0)) }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::state::Rocket;
+    use crate::skins::Skin;
+    use crate::state::{Bounds, Level, LevelState, PlanetDef, Rocket, Stage, WinCondition};
 
     fn make_rocket(orientation: f32) -> Rocket {
-        Rocket { x: 0.0, y: 0.0, speed_x: 0.0, speed_y: 0.0, orientation, landed: false, engine_on: false, fuel: 20.0 }
+        Rocket { x: 0.0, y: 0.0, speed_x: 0.0, speed_y: 0.0, orientation, landed: false, throttle: 0.0, stages: vec![], heat: 0.0, angular_velocity: 0.0, rcs_fuel: 10.0, strafe_x: 0.0, strafe_y: 0.0 }
+    }
+
+    #[test]
+    fn hold_turns_the_short_way() {
+        let mut rocket = make_rocket(350.0);
+        hold_orientation(&mut rocket, 0.1, 20.0);
+        // past 360 and round to the start
+        assert!((rocket.orientation - 359.0).abs() < 0.001);
+        hold_orientation(&mut rocket, 0.1, 20.0);
+        assert!((rocket.orientation - 8.0).abs() < 0.001);
+        let mut rocket = make_rocket(10.0);
+        hold_orientation(&mut rocket, 0.1, 300.0);
+        assert!((rocket.orientation - 1.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn hold_stops_on_target() {
+        let mut rocket = make_rocket(85.0);
+        hold_orientation(&mut rocket, 1.0, 90.0);
+        assert_eq!(rocket.orientation, 90.0);
+    }
+
+    #[test]
+    fn momentum_hold_settles_on_target() {
+        let mut rocket = make_rocket(0.0);
+        rocket.rcs_fuel = 100.0;
+        let dt = 1.0 / 60.0;
+        for _ in 0..600 {
+            hold_angular_velocity(&mut rocket, dt, 120.0);
+            rocket.orientation = (rocket.orientation + rocket.angular_velocity * dt).rem_euclid(360.0);
+        }
+        assert!((rocket.orientation - 120.0).abs() < 2.0);
+        assert!(rocket.angular_velocity.abs() < 5.0);
     }
 
     #[test]
@@ -89,4 +386,167 @@ mod tests {
         update_orientation(&mut rocket, 4.0, false, true);
         assert!((rocket.orientation - 0.0).abs() < f32::EPSILON);
     }
+
+    #[test]
+    fn throttle_increases() {
+        let mut rocket = make_rocket(0.0);
+        update_throttle(&mut rocket, 1.0, true, false);
+        assert_eq!(rocket.throttle, 0.5);
+    }
+
+    #[test]
+    fn throttle_decreases() {
+        let mut rocket = make_rocket(0.0);
+        rocket.throttle = 1.0;
+        update_throttle(&mut rocket, 1.0, false, true);
+        assert_eq!(rocket.throttle, 0.5);
+    }
+
+    #[test]
+    fn throttle_clamped_to_full() {
+        let mut rocket = make_rocket(0.0);
+        update_throttle(&mut rocket, 10.0, true, false);
+        assert_eq!(rocket.throttle, 1.0);
+    }
+
+    #[test]
+    fn throttle_clamped_to_zero() {
+        let mut rocket = make_rocket(0.0);
+        update_throttle(&mut rocket, 10.0, false, true);
+        assert_eq!(rocket.throttle, 0.0);
+    }
+
+    #[test]
+    fn analog_throttle_is_set_outright() {
+        let mut rocket = make_rocket(0.0);
+        rocket.throttle = 1.0;
+        set_throttle(&mut rocket, 0.3);
+        assert_eq!(rocket.throttle, 0.3);
+        set_throttle(&mut rocket, 1.2);
+        assert_eq!(rocket.throttle, 1.0);
+        set_throttle(&mut rocket, -0.1);
+        assert_eq!(rocket.throttle, 0.0);
+    }
+
+    #[test]
+    fn rcs_spins_up_clockwise() {
+        let mut rocket = make_rocket(0.0);
+        update_angular_velocity(&mut rocket, 0.5, false, true, false);
+        assert_eq!(rocket.angular_velocity, 60.0);
+        assert_eq!(rocket.orientation, 0.0);
+        assert_eq!(rocket.rcs_fuel, 9.5);
+    }
+
+    #[test]
+    fn rcs_spins_up_counterclockwise() {
+        let mut rocket = make_rocket(0.0);
+        update_angular_velocity(&mut rocket, 0.5, true, false, false);
+        assert_eq!(rocket.angular_velocity, -60.0);
+    }
+
+    #[test]
+    fn spin_persists_without_sas() {
+        let mut rocket = make_rocket(0.0);
+        rocket.angular_velocity = 30.0;
+        update_angular_velocity(&mut rocket, 1.0, false, false, false);
+        assert_eq!(rocket.angular_velocity, 30.0);
+        assert_eq!(rocket.rcs_fuel, 10.0);
+    }
+
+    #[test]
+    fn sas_kills_rotation() {
+        let mut rocket = make_rocket(0.0);
+        rocket.angular_velocity = 30.0;
+        update_angular_velocity(&mut rocket, 1.0, false, false, true);
+        assert_eq!(rocket.angular_velocity, 0.0);
+        // only the fuel needed to stop the spin is used
+        assert_eq!(rocket.rcs_fuel, 9.75);
+    }
+
+    #[test]
+    fn sas_limited_by_thruster_strength() {
+        let mut rocket = make_rocket(0.0);
+        rocket.angular_velocity = -300.0;
+        update_angular_velocity(&mut rocket, 1.0, false, false, true);
+        assert_eq!(rocket.angular_velocity, -180.0);
+    }
+
+    #[test]
+    fn no_rcs_without_fuel() {
+        let mut rocket = make_rocket(0.0);
+        rocket.rcs_fuel = 0.0;
+        update_angular_velocity(&mut rocket, 1.0, false, true, false);
+        assert_eq!(rocket.angular_velocity, 0.0);
+    }
+
+    #[test]
+    fn strafe_left_and_forward() {
+        let mut rocket = make_rocket(0.0);
+        update_strafe(&mut rocket, true, false, true, false);
+        assert_eq!(rocket.strafe_x, -1.0);
+        assert_eq!(rocket.strafe_y, 1.0);
+    }
+
+    #[test]
+    fn opposite_strafe_keys_cancel_out() {
+        let mut rocket = make_rocket(0.0);
+        update_strafe(&mut rocket, true, true, true, true);
+        assert_eq!(rocket.strafe_x, 0.0);
+        assert_eq!(rocket.strafe_y, 0.0);
+    }
+
+    #[test]
+    fn burn_plan_steps_between_limits() {
+        assert_eq!(update_burn_plan(Some(3.0), 1.0), Some(4.0));
+        assert_eq!(update_burn_plan(Some(1.0), -1.0), Some(1.0));
+    }
+
+    #[test]
+    fn burn_plan_runs_on_to_empty_tank() {
+        assert_eq!(update_burn_plan(Some(20.0), 1.0), None);
+        assert_eq!(update_burn_plan(None, 1.0), None);
+        assert_eq!(update_burn_plan(None, -1.0), Some(20.0));
+    }
+
+    #[test]
+    fn autopilot_flies_the_plan_it_found_at_any_frame_rate() {
+        let rocket = Rocket { stages: vec![Stage { dry_mass: 0.5, fuel_mass: 0.5, thrust: 20.0, isp: 40.0 }], ..make_rocket(0.0) };
+        let level = Level {
+            name: "Test",
+            planets: vec![PlanetDef { center_x: 300.0, center_y: 400.0, radius: 10.0, density: 1.0, image: 0, spin: 0.0, orbit: None, atmosphere: None }],
+            initial_rocket: Rocket { x: 100.0, y: 500.0, rcs_fuel: 0.0, ..rocket },
+            win_condition: WinCondition::CircleAnySpeed { x: 100.0, y: 300.0, radius: 20.0 },
+            rotation: RotationMode::Instant,
+            canisters: vec![],
+            depots: vec![],
+            hazards: vec![],
+            bounds: Bounds::Open,
+            world: vec2(400.0, 600.0),
+            star: None,
+            assists: true,
+            seed: None,
+            par: None,
+        };
+        let mut game = GameState::from_state(LevelState::new(level), Skin::all()[0].clone(), vec2(400.0, 600.0));
+        let mut solver = Solver::new(game.level.clone(), Options { max_time: 5.0, evaluations: 300, ..Default::default() });
+        solver.step(300);
+        let (_, outcome) = solver.solution().expect("the test level can be won");
+        let outcome = outcome.clone();
+        game.solver = Some(solver);
+        game.autopilot = true;
+
+        // frames that never line up with the solver's steps
+        let frames = [0.011, 0.023, 0.017, 0.031];
+        let mut event = None;
+        for dt in frames.iter().cycle().take(1000) {
+            event = update(&mut game, *dt);
+            if event.is_some() {
+                break;
+            }
+        }
+        assert!(matches!(event, Some(PhysicsEvent::Win)));
+        assert!((game.level.time as f32 - outcome.time).abs() < 1e-3);
+        let end = outcome.path.last().unwrap();
+        assert!((game.level.rocket.x - end.x).abs() < 1e-3 && (game.level.rocket.y - end.y).abs() < 1e-3);
+    }
 }
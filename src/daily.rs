@@ -0,0 +1,380 @@
+use crate::procgen::Difficulty;
+use crate::rng::Rng;
+use crate::state::LevelState;
+
+/// Seconds of level time between the samples of a replay.
+const SAMPLE_INTERVAL: f64 = 0.1;
+
+/// How many of the best days the leaderboard keeps.
+pub const LEADERBOARD_SIZE: usize = 10;
+
+/// Today's date in UTC as `YYYY-MM-DD`, the same for everyone playing that day.
+pub fn today() -> String {
+    let days = (macroquad::miniquad::date::now() / 86400.0).floor() as i64;
+    let (year, month, day) = date_from_days(days);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// The calendar date `days` after 1970-01-01.
+fn date_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's civil_from_days, counting in 400 year eras from 0000-03-01
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153; // 0 = March
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Mixed into the date before hashing it, so daily seeds come from their own stream.
+const DAILY_TAG: u64 = 0x6461_696C_7920_6772; // "daily gr"
+
+/// The level seed for a date: its digits hashed with `DAILY_TAG`, and always
+/// above the nine digits the Endless menu takes, so a day's level can't be
+/// played in Endless mode ahead of the real attempt.
+pub fn seed(date: &str) -> u64 {
+    let digits: u64 = date.chars().filter(char::is_ascii_digit).collect::<String>().parse().unwrap_or(0);
+    Rng::new(digits ^ DAILY_TAG).next_u64() | 1 << 63
+}
+
+/// How hard the daily levels are.
+pub fn difficulty() -> Difficulty {
+    Difficulty::all()[1]
+}
+
+/// Points for a daily run: none for a loss, otherwise more for arriving sooner
+/// and with more of the fuel (as a share of the tank) left.
+pub fn score(won: bool, time: f32, fuel_left: f32) -> u32 {
+    if !won {
+        return 0;
+    }
+    let time_bonus = (1000.0 - time * 10.0).max(0.0);
+    (1000.0 + time_bonus + fuel_left * 1000.0).round() as u32
+}
+
+/// How one day's single attempt went.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DailyResult {
+    pub date: String,
+    pub won: bool,
+    pub time: f32,      // seconds until the run ended
+    pub fuel_left: f32, // share of the starting fuel, 0 to 1
+    pub score: u32,
+}
+
+impl DailyResult {
+    pub fn description(&self) -> String {
+        if self.won {
+            format!("Reached the goal in {:.1}s with {:.0}% fuel left: {} points", self.time, self.fuel_left * 100.0, self.score)
+        } else {
+            format!("Didn't make it after {:.1}s: 0 points", self.time)
+        }
+    }
+
+    fn to_line(&self) -> String {
+        format!("{} {} {} {} {}", self.date, self.won, self.time, self.fuel_left, self.score)
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let result = Self {
+            date: fields.next()?.to_string(),
+            won: fields.next()?.parse().ok()?,
+            time: fields.next()?.parse().ok()?,
+            fuel_left: fields.next()?.parse().ok()?,
+            score: fields.next()?.parse().ok()?,
+        };
+        fields.next().is_none().then_some(result)
+    }
+}
+
+/// Every daily result played on this device, kept in `quad_storage`.
+#[derive(Clone, Default)]
+pub struct Leaderboard {
+    pub results: Vec<DailyResult>, // in the order they were played
+}
+
+impl Leaderboard {
+    pub fn load() -> Self {
+        let storage = quad_storage::STORAGE.lock().unwrap();
+        storage.get("daily_results").map_or_else(Self::default, |v| Self::parse(&v))
+    }
+
+    fn parse(value: &str) -> Self {
+        Self { results: value.lines().filter_map(DailyResult::parse).collect() }
+    }
+
+    /// Records a loss for `date` as its run starts, so closing the game mid-run
+    /// can't earn another go. The run's real result replaces it when it ends.
+    pub fn start(&mut self, date: &str) {
+        self.set(DailyResult { date: date.to_string(), won: false, time: 0.0, fuel_left: 0.0, score: 0 });
+    }
+
+    /// Records a day's run, replacing what that day had so far.
+    pub fn set(&mut self, result: DailyResult) {
+        self.replace(result);
+        let lines: Vec<String> = self.results.iter().map(DailyResult::to_line).collect();
+        let mut storage = quad_storage::STORAGE.lock().unwrap();
+        storage.set("daily_results", &lines.join("\n"));
+    }
+
+    fn replace(&mut self, result: DailyResult) {
+        match self.results.iter_mut().find(|old| old.date == result.date) {
+            Some(old) => *old = result,
+            None => self.results.push(result),
+        }
+    }
+
+    pub fn on(&self, date: &str) -> Option<&DailyResult> {
+        self.results.iter().find(|result| result.date == date)
+    }
+
+    /// The best days first, the earlier one first on a tie.
+    pub fn ranked(&self) -> Vec<&DailyResult> {
+        let mut ranked: Vec<&DailyResult> = self.results.iter().collect();
+        ranked.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.date.cmp(&b.date)));
+        ranked.truncate(LEADERBOARD_SIZE);
+        ranked
+    }
+}
+
+/// Where the rocket was at one moment of a replay.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    pub time: f32,
+    pub x: f32,
+    pub y: f32,
+    pub orientation: f32,
+    pub throttle: f32,
+}
+
+/// A daily run, sampled so it can be written to a text file and compared with
+/// someone else's run of the same level.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub date: String,
+    pub seed: u64,
+    pub samples: Vec<Sample>,
+    pub result: Option<DailyResult>, // set once the run is over
+}
+
+impl Replay {
+    pub fn new(date: &str) -> Self {
+        Self { date: date.to_string(), seed: seed(date), samples: Vec::new(), result: None }
+    }
+
+    /// Adds a sample if one is due, until the run is over.
+    pub fn record(&mut self, level: &LevelState) {
+        let due = self.samples.last().is_none_or(|last| level.time - last.time as f64 >= SAMPLE_INTERVAL);
+        if self.result.is_some() || !due {
+            return;
+        }
+        let rocket = &level.rocket;
+        self.samples.push(Sample {
+            time: level.time as f32,
+            x: rocket.x,
+            y: rocket.y,
+            orientation: rocket.orientation,
+            throttle: rocket.throttle,
+        });
+    }
+
+    /// Ends the run, scoring it on how `level` stands now.
+    pub fn finish(&mut self, level: &LevelState, won: bool) -> DailyResult {
+        self.record(level);
+        let tank: f32 = level.level.initial_rocket.stages.iter().map(|stage| stage.fuel_mass).sum();
+        let fuel: f32 = level.rocket.stages.iter().map(|stage| stage.fuel_mass).sum();
+        let fuel_left = if tank > 0.0 { (fuel / tank).clamp(0.0, 1.0) } else { 0.0 };
+        let time = level.time as f32;
+        let result = DailyResult { date: self.date.clone(), won, time, fuel_left, score: score(won, time, fuel_left) };
+        self.result = Some(result.clone());
+        result
+    }
+
+    /// Seconds spent with the engine on, as far as the samples tell.
+    pub fn burn_time(&self) -> f32 {
+        self.samples.windows(2).filter(|pair| pair[0].throttle > 0.0).map(|pair| pair[1].time - pair[0].time).sum()
+    }
+
+    /// Where the rocket was at `time`, between the samples either side of it.
+    pub fn position_at(&self, time: f32) -> Option<(f32, f32)> {
+        let after = self.samples.iter().position(|sample| sample.time >= time)?;
+        let b = self.samples[after];
+        let Some(a) = after.checked_sub(1).map(|i| self.samples[i]) else { return Some((b.x, b.y)) };
+        let t = (time - a.time) / (b.time - a.time);
+        Some((a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t))
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("gravioli daily replay\ndate {}\nseed {}\n", self.date, self.seed);
+        if let Some(result) = &self.result {
+            text += &format!("result {}\n", result.to_line());
+        }
+        text += "samples time x y orientation throttle\n";
+        for s in &self.samples {
+            text += &format!("{:.3} {:.2} {:.2} {:.1} {:.2}\n", s.time, s.x, s.y, s.orientation, s.throttle);
+        }
+        text
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        if lines.next() != Some("gravioli daily replay") {
+            return Err("not a gravioli daily replay".to_string());
+        }
+        let mut replay = Self { date: String::new(), seed: 0, samples: Vec::new(), result: None };
+        let mut in_samples = false;
+        for (number, line) in lines.enumerate() {
+            let bad_line = || format!("line {} doesn't make sense: {}", number + 2, line);
+            if in_samples {
+                let values: Vec<f32> = line.split_whitespace().map(str::parse).collect::<Result<_, _>>().map_err(|_| bad_line())?;
+                let [time, x, y, orientation, throttle] = values[..] else { return Err(bad_line()) };
+                replay.samples.push(Sample { time, x, y, orientation, throttle });
+                continue;
+            }
+            match line.split_once(' ') {
+                Some(("date", date)) => replay.date = date.to_string(),
+                Some(("seed", seed)) => replay.seed = seed.parse().map_err(|_| bad_line())?,
+                Some(("result", result)) => replay.result = Some(DailyResult::parse(result).ok_or_else(bad_line)?),
+                Some(("samples", _)) => in_samples = true,
+                _ => return Err(bad_line()),
+            }
+        }
+        Ok(replay)
+    }
+
+    /// Writes the replay next to the game, returning the file's name.
+    pub fn export(&self) -> std::io::Result<String> {
+        let score = self.result.as_ref().map_or(0, |result| result.score);
+        let path = format!("gravioli-daily-{}-{}.txt", self.date, score);
+        std::fs::write(&path, self.to_text())?;
+        Ok(path)
+    }
+}
+
+/// The first time two runs of the same level are more than `distance` apart,
+/// None if they stay together for as long as both last.
+pub fn divergence(a: &Replay, b: &Replay, distance: f32) -> Option<f32> {
+    a.samples.iter().find_map(|sample| {
+        let (x, y) = b.position_at(sample.time)?;
+        let apart = ((sample.x - x).powi(2) + (sample.y - y).powi(2)).sqrt();
+        (apart > distance).then_some(sample.time)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::procgen;
+
+    fn make_sample(time: f32, x: f32, throttle: f32) -> Sample {
+        Sample { time, x, y: 0.0, orientation: 90.0, throttle }
+    }
+
+    fn make_result(date: &str, score: u32) -> DailyResult {
+        DailyResult { date: date.to_string(), won: score > 0, time: 30.0, fuel_left: 0.5, score }
+    }
+
+    #[test]
+    fn dates_from_days() {
+        assert_eq!(date_from_days(0), (1970, 1, 1));
+        assert_eq!(date_from_days(59), (1970, 3, 1));
+        assert_eq!(date_from_days(11_016), (2000, 2, 29));
+        assert_eq!(date_from_days(20_745), (2026, 10, 19));
+        assert_eq!(date_from_days(-1), (1969, 12, 31));
+    }
+
+    #[test]
+    fn seed_from_date() {
+        assert_eq!(seed("2026-10-19"), seed("2026-10-19"));
+        assert_ne!(seed("2026-10-19"), seed("2026-10-20"));
+        // out of reach of a seed typed into the Endless menu
+        assert!(seed("2026-10-19") > 999_999_999);
+        assert_ne!(seed("2026-10-19"), 20261019);
+    }
+
+    #[test]
+    fn same_day_same_level() {
+        let a = procgen::generate(seed("2026-10-19"), difficulty());
+        let b = procgen::generate(seed("2026-10-19"), difficulty());
+        let centers = |level: &crate::state::Level| level.planets.iter().map(|p| (p.center_x, p.center_y)).collect::<Vec<_>>();
+        assert_eq!(centers(&a), centers(&b));
+    }
+
+    #[test]
+    fn scores() {
+        assert_eq!(score(false, 10.0, 1.0), 0);
+        assert_eq!(score(true, 20.0, 0.5), 1000 + 800 + 500);
+        assert!(score(true, 10.0, 0.5) > score(true, 20.0, 0.5));
+        assert!(score(true, 10.0, 0.6) > score(true, 10.0, 0.5));
+        // slow wins still beat any loss
+        assert_eq!(score(true, 500.0, 0.0), 1000);
+    }
+
+    #[test]
+    fn leaderboard_round_trip_and_ranking() {
+        let stored = "2026-10-17 true 30 0.5 1500\n2026-10-18 false 12.5 0 0\n2026-10-19 true 20 0.25 1550\n";
+        let board = Leaderboard::parse(stored);
+        assert_eq!(board.results.len(), 3);
+        assert_eq!(board.results[1], DailyResult { date: "2026-10-18".to_string(), won: false, time: 12.5, fuel_left: 0.0, score: 0 });
+        let ranked: Vec<&str> = board.ranked().iter().map(|result| result.date.as_str()).collect();
+        assert_eq!(ranked, vec!["2026-10-19", "2026-10-17", "2026-10-18"]);
+        let lines: Vec<String> = board.results.iter().map(DailyResult::to_line).collect();
+        assert_eq!(Leaderboard::parse(&lines.join("\n")).results, board.results);
+        assert!(Leaderboard::parse("garbage\n2026-10-19 true 20").results.is_empty());
+    }
+
+    #[test]
+    fn result_replaces_the_days_provisional_loss() {
+        let mut board = Leaderboard { results: vec![make_result("2026-10-18", 1500)] };
+        board.replace(DailyResult { date: "2026-10-19".to_string(), won: false, time: 0.0, fuel_left: 0.0, score: 0 });
+        assert_eq!(board.on("2026-10-19").unwrap().score, 0);
+        board.replace(make_result("2026-10-19", 1700));
+        assert_eq!(board.results.len(), 2);
+        assert_eq!(board.results[1], make_result("2026-10-19", 1700));
+    }
+
+    #[test]
+    fn leaderboard_keeps_the_best_days() {
+        let board = Leaderboard { results: (0..15).map(|day| make_result(&format!("2026-10-{:02}", day + 1), day * 100)).collect() };
+        let ranked = board.ranked();
+        assert_eq!(ranked.len(), LEADERBOARD_SIZE);
+        assert_eq!(ranked[0].score, 1400);
+    }
+
+    #[test]
+    fn replay_round_trip() {
+        let mut replay = Replay::new("2026-10-19");
+        replay.samples = vec![make_sample(0.0, 100.0, 0.0), make_sample(0.1, 101.0, 1.0), make_sample(0.2, 103.0, 0.0)];
+        replay.result = Some(make_result("2026-10-19", 1550));
+        let parsed = Replay::parse(&replay.to_text()).expect("should parse");
+        assert_eq!(parsed, replay);
+        assert!(Replay::parse("hello").is_err());
+        assert!(Replay::parse(&(replay.to_text() + "1 2 three 4 5\n")).is_err());
+    }
+
+    #[test]
+    fn replay_measures_burns_and_positions() {
+        let mut replay = Replay::new("2026-10-19");
+        replay.samples = vec![make_sample(0.0, 0.0, 1.0), make_sample(1.0, 10.0, 1.0), make_sample(2.0, 30.0, 0.0)];
+        assert!((replay.burn_time() - 2.0).abs() < 1e-6);
+        assert_eq!(replay.position_at(1.5), Some((20.0, 0.0)));
+        assert_eq!(replay.position_at(3.0), None);
+    }
+
+    #[test]
+    fn runs_part_ways() {
+        let mut a = Replay::new("2026-10-19");
+        a.samples = (0..10).map(|i| make_sample(i as f32, i as f32 * 10.0, 0.0)).collect();
+        let mut b = a.clone();
+        assert_eq!(divergence(&a, &b, 5.0), None);
+        for sample in b.samples.iter_mut().skip(6) {
+            sample.x += 20.0;
+        }
+        assert_eq!(divergence(&a, &b, 5.0), Some(6.0));
+    }
+}
@@ -1,31 +1,149 @@
 use macroquad::prelude::*;
-use crate::state::{Planet, PlanetDef, Rocket, WinCondition};
+use crate::state::{Bounds, Crash, Debris, FuelCanister, FuelDepot, GameState, Planet, PlanetDef, Rocket, RotationMode, TrailPoint, WinCondition};
+use crate::camera::{self, Camera, CameraMode};
+use crate::daily::Leaderboard;
+use crate::hazards::Asteroid;
 use crate::images::Images;
-use crate::physics;
+use crate::particles::Particles;
+use crate::physics::{self, PathEnd, Prediction};
+use crate::skins::{self, Skin};
+use crate::solver::Solver;
 
-pub fn draw(planet_defs: &[PlanetDef], rocket: &Rocket, win_condition: &WinCondition, images: &Images, elapsed: f64, show_hud: bool, show_path: bool) {
-    let planets: Vec<_> = planet_defs.iter().map(|p| p.planet_at(elapsed)).collect();
+pub fn draw(game: &GameState, images: &Images) {
+    let planet_defs = &game.level.level.planets;
+    let rocket = &game.level.rocket;
+    let win_condition = &game.level.level.win_condition;
+    let elapsed = game.level.elapsed();
+    let planets = game.level.current_planets();
     clear_background(BLACK);
-    draw_texture_ex(&images.bg_texture, 0.0, 0.0, WHITE, DrawTextureParams {
-        dest_size: Some(Vec2::new(screen_width(), screen_height())),
-        ..Default::default()
-    });
+    let screen = vec2(screen_width(), screen_height());
+    draw_background(images, &game.camera, screen);
+    set_camera(&game.camera.to_camera2d(screen));
+    draw_bounds(&game.level.level.bounds);
+    if game.show_field {
+        draw_gravity_field(&planets, &game.camera, screen);
+    }
+    if game.show_orbits {
+        draw_orbits(planet_defs);
+    }
     draw_win_condition(win_condition);
-    for planet in &planets {
+    for (i, planet) in planets.iter().enumerate() {
+        draw_atmosphere(planet);
         draw_planet(planet, &images.planet_textures[planet.image]);
+        if let Some(star) = game.level.level.star.filter(|&star| star != i) {
+            draw_planet_shading(planet, &planets[star]);
+        }
     }
-    if show_path {
-        draw_projected_path(rocket, planet_defs, elapsed);
+    for depot in &game.level.level.depots {
+        draw_depot(depot, &planets[depot.planet]);
     }
-    draw_rocket(rocket);
-    if show_hud {
-        draw_hud(elapsed, rocket, &planets);
+    for (canister, collected) in game.level.level.canisters.iter().zip(&game.level.collected) {
+        if !collected {
+            draw_canister(canister, elapsed);
+        }
     }
-    if elapsed < 5.0 {
+    if game.show_past_trails {
+        for trail in &game.level.past_trails {
+            draw_past_trail(trail);
+        }
+    }
+    draw_trail(&game.level.trail, elapsed);
+    if let Some(marker) = game.last_crash {
+        draw_crash_marker(marker);
+    }
+    if let Some((_, outcome)) = game.solver.as_ref().and_then(|solver| solver.solution()) {
+        draw_solution_path(&outcome.path);
+    }
+    let prediction = (game.show_path && game.crash.is_none()).then(|| physics::predict(&game.level, elapsed, game.path_horizon, 0.0));
+    if let Some(prediction) = &prediction {
+        if game.show_orbits {
+            draw_ghost_planets(prediction, planet_defs, elapsed);
+        }
+        draw_prediction(prediction, planet_defs, elapsed);
+        if rocket.throttle > 0.0 {
+            let burn = game.burn_plan.unwrap_or(f32::INFINITY);
+            draw_burn_prediction(&physics::predict(&game.level, elapsed, game.path_horizon, burn));
+        }
+    }
+    for asteroid in &game.level.asteroids {
+        draw_asteroid(asteroid);
+    }
+    for piece in &game.level.debris {
+        draw_debris(piece);
+    }
+    draw_particles(&game.particles);
+    if game.crash.is_none() {
+        draw_rocket(rocket, &game.skin, images);
+    }
+    set_default_camera();
+    draw_offscreen_indicators(game, &planets, screen);
+    if game.show_hud {
+        draw_hud(game, elapsed, rocket, &planets);
+    }
+    if game.show_minimap {
+        draw_minimap(game, &planets, prediction.as_ref(), screen);
+    }
+    if let Some(solver) = &game.solver {
+        draw_solver_status(solver, game.autopilot);
+    }
+    if let Some(crash) = &game.crash {
+        draw_crash_text(crash);
+    } else if elapsed < 5.0 {
         draw_help_text(&win_condition.description(), elapsed);
     }
 }
 
+/// Three background layers that scroll slower the further away they are: the
+/// backdrop shifts a little across the whole world and the star fields tile.
+fn draw_background(images: &Images, camera: &Camera, screen: Vec2) {
+    let overscan = screen * 0.1;
+    let shift = (camera.center / camera.world).clamp(Vec2::ZERO, Vec2::ONE) * overscan;
+    draw_texture_ex(&images.backdrop_texture, -shift.x, -shift.y, WHITE, DrawTextureParams {
+        dest_size: Some(screen + overscan),
+        ..Default::default()
+    });
+    for (texture, depth) in images.star_textures.iter().zip([0.1, 0.25]) {
+        let size = texture.size();
+        let offset = (camera.center * depth * camera.zoom).rem_euclid(size);
+        let mut x = -offset.x;
+        while x < screen.x {
+            let mut y = -offset.y;
+            while y < screen.y {
+                draw_texture(texture, x, y, WHITE);
+                y += size.y;
+            }
+            x += size.x;
+        }
+    }
+}
+
+fn draw_bounds(bounds: &Bounds) {
+    match bounds {
+        Bounds::Open => {}
+        Bounds::Wrap(rect) => {
+            // dashed, since the rocket passes through
+            let dash = 12.0;
+            let color = Color::new(0.4, 0.8, 1.0, 0.6);
+            let mut d = 0.0;
+            while d < rect.w {
+                let end = (d + dash).min(rect.w);
+                draw_line(rect.x + d, rect.y, rect.x + end, rect.y, 2.0, color);
+                draw_line(rect.x + d, rect.bottom(), rect.x + end, rect.bottom(), 2.0, color);
+                d += dash * 2.0;
+            }
+            let mut d = 0.0;
+            while d < rect.h {
+                let end = (d + dash).min(rect.h);
+                draw_line(rect.x, rect.y + d, rect.x, rect.y + end, 2.0, color);
+                draw_line(rect.right(), rect.y + d, rect.right(), rect.y + end, 2.0, color);
+                d += dash * 2.0;
+            }
+        }
+        Bounds::Wall { rect, .. } => draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 6.0, LIGHTGRAY),
+        Bounds::Lose(rect) => draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 3.0, RED),
+    }
+}
+
 fn draw_win_condition(condition: &WinCondition) {
     match condition {
         WinCondition::Circle { x, y, radius, .. }
@@ -35,72 +153,621 @@ fn draw_win_condition(condition: &WinCondition) {
     }
 }
 
+fn draw_orbits(planet_defs: &[PlanetDef]) {
+    let color = Color::new(1.0, 1.0, 1.0, 0.25);
+    for def in planet_defs {
+        if let Some(orbit) = &def.orbit {
+            draw_circle_lines(def.center_x, def.center_y, orbit.radius, 1.0, color);
+        }
+    }
+}
+
+/// Shows where each moving planet will be when the predicted path passes closest to it.
+fn draw_ghost_planets(prediction: &Prediction, planet_defs: &[PlanetDef], elapsed: f64) {
+    for approach in &prediction.approaches {
+        let def = &planet_defs[approach.planet];
+        if def.orbit.is_none() {
+            continue;
+        }
+        let time = prediction.time_at(approach.index);
+        let ghost = def.planet_at(elapsed + time as f64);
+        draw_circle(ghost.x, ghost.y, ghost.radius, Color::new(0.6, 0.8, 1.0, 0.15));
+        draw_circle_lines(ghost.x, ghost.y, ghost.radius, 1.0, Color::new(0.6, 0.8, 1.0, 0.5));
+        draw_text(&format!("+{:.1}s", time), ghost.x + ghost.radius + 4.0, ghost.y, 18.0, Color::new(0.6, 0.8, 1.0, 0.8));
+    }
+}
+
+/// A grid of arrows over the visible area showing which way and how hard gravity
+/// pulls at each point, so players can see how the wells of several planets combine.
+fn draw_gravity_field(planets: &[Planet], camera: &Camera, screen: Vec2) {
+    // constant spacing and arrow size on screen whatever the zoom
+    let spacing = 48.0 / camera.zoom;
+    let top_left = camera.screen_to_world(Vec2::ZERO, screen);
+    let bottom_right = camera.screen_to_world(screen, screen);
+    let mut x = (top_left.x / spacing).floor() * spacing;
+    while x <= bottom_right.x {
+        let mut y = (top_left.y / spacing).floor() * spacing;
+        while y <= bottom_right.y {
+            let inside = planets.iter().any(|p| (x - p.x).powi(2) + (y - p.y).powi(2) < p.radius * p.radius);
+            if !inside {
+                let (ax, ay) = physics::gravity_field(x, y, planets);
+                draw_field_arrow(vec2(x, y), vec2(ax, ay), camera.zoom);
+            }
+            y += spacing;
+        }
+        x += spacing;
+    }
+}
+
+fn draw_field_arrow(at: Vec2, accel: Vec2, zoom: f32) {
+    let strength = accel.length();
+    if strength < 0.1 {
+        return;
+    }
+    // log scale, since the pull grows without bound near a planet
+    let level = (strength.ln_1p() / 6.0).min(1.0);
+    let color = Color::new(0.3 + 0.7 * level, 0.8 - 0.5 * level, 1.0 - 0.8 * level, 0.25 + 0.5 * level);
+    let direction = accel / strength;
+    let length = (6.0 + 14.0 * level) / zoom;
+    let tip = at + direction * length;
+    draw_line(at.x, at.y, tip.x, tip.y, 1.0 / zoom, color);
+    let side = direction.perp() * 3.0 / zoom;
+    draw_triangle(tip, tip - direction * 5.0 / zoom + side, tip - direction * 5.0 / zoom - side, color);
+}
+
+fn draw_atmosphere(planet: &Planet) {
+    let Some(atmosphere) = &planet.atmosphere else { return };
+    // concentric rings that fade out with the density falloff
+    let rings = 12;
+    for i in (0..rings).rev() {
+        let altitude = atmosphere.thickness * (i as f32 + 1.0) / rings as f32;
+        let alpha = 0.25 * (-altitude / atmosphere.scale_height).exp();
+        draw_circle(planet.x, planet.y, planet.radius + altitude, Color::new(0.4, 0.7, 1.0, alpha));
+    }
+}
+
 fn draw_planet(planet: &Planet, texture: &Texture2D) {
     let size = planet.radius * 2.0;
     draw_texture_ex(texture, planet.x - planet.radius, planet.y - planet.radius, WHITE, DrawTextureParams {
         dest_size: Some(Vec2::new(size, size)),
+        rotation: planet.rotation.to_radians(),
         ..Default::default()
     });
 }
 
-fn draw_rocket(rocket: &Rocket) {
-    let body_width = 10.0;
-    let body_height = 30.0;
-    let nose_height = 10.0;
+/// Darkens the side of the planet facing away from the star, fading across the
+/// terminator.
+fn draw_planet_shading(planet: &Planet, star: &Planet) {
+    let segments = 32;
+    let light = vec2(star.x - planet.x, star.y - planet.y).normalize_or_zero();
+    let shadow = |alpha: f32| Color::new(0.0, 0.0, 0.05, alpha);
+    let mut vertices = vec![Vertex::new(planet.x, planet.y, 0.0, 0.0, 0.0, shadow(0.3))];
+    for i in 0..=segments {
+        let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+        let normal = vec2(angle.cos(), angle.sin());
+        let alpha = (0.3 - 0.55 * normal.dot(light)).clamp(0.0, 0.85);
+        let edge = vec2(planet.x, planet.y) + normal * planet.radius;
+        vertices.push(Vertex::new(edge.x, edge.y, 0.0, 0.0, 0.0, shadow(alpha)));
+    }
+    let indices = (1..=segments as u16).flat_map(|i| [0, i, i + 1]).collect();
+    draw_mesh(&Mesh { vertices, indices, texture: None });
+}
+
+fn draw_canister(canister: &FuelCanister, elapsed: f64) {
+    let (x, y) = canister.position_at(elapsed);
+    // gentle pulse so pickups stand out against the stars
+    let glow = 0.3 + 0.2 * (elapsed as f32 * 4.0).sin();
+    draw_circle(x, y, 14.0, Color::new(1.0, 0.8, 0.2, glow));
+    draw_rectangle(x - 5.0, y - 8.0, 10.0, 16.0, GOLD);
+    draw_rectangle_lines(x - 5.0, y - 8.0, 10.0, 16.0, 1.0, ORANGE);
+}
+
+fn draw_depot(depot: &FuelDepot, planet: &Planet) {
+    let (x, y) = physics::depot_position(depot, planet);
+    let (nx, ny) = (depot.angle.cos(), depot.angle.sin());
+    // a flat pad tangent to the surface with a marker light above it
+    let (tx, ty) = (-ny * 12.0, nx * 12.0);
+    draw_line(x - tx, y - ty, x + tx, y + ty, 4.0, GOLD);
+    draw_circle(x + nx * 8.0, y + ny * 8.0, 3.0, LIME);
+}
+
+fn draw_rocket(rocket: &Rocket, skin: &Skin, images: &Images) {
+    let size = skins::FRAME_SIZE * skins::SCALE;
+    let offset = skin.pivot * skins::SCALE;
+    // glow red as the hull heats up
+    let cool = 1.0 - 0.8 * rocket.heat.min(1.0);
+    draw_texture_ex(
+        &images.rocket_atlas,
+        rocket.x - offset.x,
+        rocket.y - offset.y,
+        Color::new(1.0, cool, cool, 1.0),
+        DrawTextureParams {
+            dest_size: Some(size),
+            source: Some(skin.source_rect()),
+            rotation: rocket.orientation.to_radians(),
+            pivot: Some(vec2(rocket.x, rocket.y)),
+            ..Default::default()
+        },
+    );
+}
+
+/// Consecutive trail points further apart than this are a wrap-around, not a path.
+const TRAIL_GAP: f32 = 200.0;
+
+/// The path flown so far, orange where the engine was burning and otherwise
+/// shading from blue when slow to red when fast, fading out with age.
+fn draw_trail(trail: &[TrailPoint], elapsed: f64) {
+    let max_age = 30.0;
+    for pair in trail.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        if (b.x - a.x).abs() > TRAIL_GAP || (b.y - a.y).abs() > TRAIL_GAP {
+            continue;
+        }
+        let fast = (b.speed / 300.0).min(1.0);
+        let color = if b.burning { ORANGE } else { Color::new(0.3 + 0.7 * fast, 0.5, 1.0 - 0.7 * fast, 1.0) };
+        let alpha = (1.0 - (elapsed - b.time) as f32 / max_age).max(0.0) * 0.8;
+        draw_line(a.x, a.y, b.x, b.y, 2.0, Color { a: alpha, ..color });
+    }
+}
+
+fn draw_past_trail(trail: &[TrailPoint]) {
+    let color = Color::new(1.0, 1.0, 1.0, 0.15);
+    for pair in trail.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        if (b.x - a.x).abs() <= TRAIL_GAP && (b.y - a.y).abs() <= TRAIL_GAP {
+            draw_line(a.x, a.y, b.x, b.y, 1.0, color);
+        }
+    }
+}
+
+/// The solver's winning run, as a dotted line.
+fn draw_solution_path(path: &[Vec2]) {
+    let color = Color::new(0.4, 1.0, 0.8, 0.7);
+    for pair in path.chunks(2).step_by(3) {
+        if let [from, to] = pair {
+            draw_line(from.x, from.y, to.x, to.y, 2.0, color);
+        }
+    }
+}
+
+fn draw_solver_status(solver: &Solver, autopilot: bool) {
+    let text = match solver.solution() {
+        _ if !solver.finished() => format!("Searching for a solution... {:.0}%", solver.progress() * 100.0),
+        Some((plan, outcome)) => {
+            let action = if autopilot { "autopilot flying" } else { "J to fly it" };
+            format!("Solution: {} burns, goal at {:.1}s ({})", plan.burns.len(), outcome.time, action)
+        }
+        None => "No solution found from here".to_string(),
+    };
+    let dims = measure_text(&text, None, 26, 1.0);
+    draw_text(&text, (screen_width() - dims.width) / 2.0, 90.0, 26.0, Color::new(0.4, 1.0, 0.8, 1.0));
+}
+
+fn draw_crash_marker(at: Vec2) {
+    let color = Color::new(1.0, 0.3, 0.3, 0.6);
+    draw_line(at.x - 8.0, at.y - 8.0, at.x + 8.0, at.y + 8.0, 2.0, color);
+    draw_line(at.x - 8.0, at.y + 8.0, at.x + 8.0, at.y - 8.0, 2.0, color);
+    draw_text("Last crash", at.x + 12.0, at.y + 5.0, 18.0, color);
+}
+
+fn draw_crash_text(crash: &Crash) {
+    let title = crash.cause.description();
+    let dims = measure_text(title, None, 48, 1.0);
+    draw_text(title, (screen_width() - dims.width) / 2.0, screen_height() / 3.0, 48.0, RED);
+    // give the slow-motion replay a moment before nagging
+    if crash.timer > 1.5 {
+        let hint = "Press R to try again";
+        let dims = measure_text(hint, None, 28, 1.0);
+        draw_text(hint, (screen_width() - dims.width) / 2.0, screen_height() / 3.0 + 50.0, 28.0, WHITE);
+    }
+}
 
-    let angle = rocket.orientation.to_radians();
-    let rotate = |lx: f32, ly: f32| -> (f32, f32) {
+fn draw_particles(particles: &Particles) {
+    for particle in particles.alive() {
+        let fade = 1.0 - particle.age / particle.lifetime;
+        let color = Color { a: particle.color.a * fade, ..particle.color };
+        draw_circle(particle.x, particle.y, particle.size, color);
+    }
+}
+
+fn draw_asteroid(asteroid: &Asteroid) {
+    draw_poly(asteroid.x, asteroid.y, 7, asteroid.radius, asteroid.angle, Color::new(0.45, 0.4, 0.35, 1.0));
+    draw_poly_lines(asteroid.x, asteroid.y, 7, asteroid.radius, asteroid.angle, 1.0, Color::new(0.7, 0.65, 0.6, 1.0));
+}
+
+fn draw_debris(piece: &Debris) {
+    let angle = piece.orientation.to_radians();
+    let rotate = |lx: f32, ly: f32| -> Vec2 {
         let rx = lx * angle.cos() - ly * angle.sin();
         let ry = lx * angle.sin() + ly * angle.cos();
-        (rocket.x + rx, rocket.y + ry)
+        Vec2::new(piece.x + rx, piece.y + ry)
     };
+    let (w, h) = (5.0, 12.0);
+    let (bl, br, tl, tr) = (rotate(-w, h), rotate(w, h), rotate(-w, 0.0), rotate(w, 0.0));
+    draw_triangle(bl, br, tr, GRAY);
+    draw_triangle(bl, tr, tl, GRAY);
+}
+
+fn draw_prediction(prediction: &Prediction, planet_defs: &[PlanetDef], elapsed: f64) {
+    let points = &prediction.points;
+    // faint ring around the body the apsides are measured against
+    if let Some(index) = prediction.dominant {
+        let planet = planet_defs[index].planet_at(elapsed);
+        draw_circle_lines(planet.x, planet.y, planet.radius + 6.0, 1.0, Color::new(0.4, 0.7, 1.0, 0.4));
+    }
+    // one dot every quarter second, green once inside the goal (yellow if too fast to count)
+    for (i, p) in points.iter().enumerate().skip(14).step_by(15) {
+        let color = match prediction.goal_entry {
+            Some((entry, true)) if i >= entry => GREEN,
+            Some((entry, false)) if i >= entry => YELLOW,
+            _ => WHITE,
+        };
+        draw_circle(p.x, p.y, 1.5, color);
+    }
+    if let Some((entry, wins)) = prediction.goal_entry {
+        let p = &points[entry];
+        let color = if wins { GREEN } else { YELLOW };
+        draw_circle_lines(p.x, p.y, 6.0, 2.0, color);
+        draw_text(&format!("Goal in {:.1}s", prediction.time_at(entry)), p.x + 10.0, p.y - 10.0, 20.0, color);
+    }
 
-    // Body rectangle (two triangles)
-    let bl = rotate(-body_width / 2.0, 0.0);
-    let br = rotate(body_width / 2.0, 0.0);
-    let tl = rotate(-body_width / 2.0, -body_height);
-    let tr = rotate(body_width / 2.0, -body_height);
+    for apsis in &prediction.apsides {
+        let p = &points[apsis.index];
+        let label = if apsis.periapsis { "Pe" } else { "Ap" };
+        draw_rectangle(p.x - 3.0, p.y - 3.0, 6.0, 6.0, SKYBLUE);
+        draw_text(&format!("{} {:.0}", label, apsis.altitude), p.x + 8.0, p.y + 4.0, 18.0, SKYBLUE);
+    }
 
-    draw_triangle(bl.into(), br.into(), tr.into(), WHITE);
-    draw_triangle(bl.into(), tr.into(), tl.into(), WHITE);
+    for approach in &prediction.approaches {
+        // skip planets the rocket is heading straight into or doesn't come near
+        let is_end = approach.index + 1 == points.len() && prediction.end.is_some();
+        if is_end || approach.altitude > 300.0 {
+            continue;
+        }
+        let p = &points[approach.index];
+        let planet = planet_defs[approach.planet].planet_at(elapsed + prediction.time_at(approach.index) as f64);
+        draw_line(p.x, p.y, planet.x, planet.y, 1.0, Color::new(1.0, 1.0, 1.0, 0.25));
+        draw_circle_lines(p.x, p.y, 4.0, 1.0, LIGHTGRAY);
+        draw_text(&format!("{:.0} px", approach.altitude), p.x + 8.0, p.y - 6.0, 18.0, LIGHTGRAY);
+    }
 
-    // Nose cone
-    let tip = rotate(0.0, -body_height - nose_height);
-    draw_triangle(tl.into(), tr.into(), tip.into(), WHITE);
+    draw_path_end(prediction);
+}
 
-    // Engine flames
-    if rocket.engine_on {
-        let flame_height = 15.0;
-        let fl = rotate(-body_width / 3.0, 0.0);
-        let fr = rotate(body_width / 3.0, 0.0);
-        let ftip = rotate(0.0, flame_height);
-        draw_triangle(fl.into(), fr.into(), ftip.into(), ORANGE);
+/// The trajectory if the engine keeps burning, drawn as a line so it stands apart
+/// from the dotted coasting prediction.
+fn draw_burn_prediction(prediction: &Prediction) {
+    let points = &prediction.points;
+    let cutoff = prediction.cutoff.unwrap_or(0);
+    for (i, pair) in points.windows(2).enumerate() {
+        let color = if i < cutoff { ORANGE } else { Color::new(1.0, 0.63, 0.0, 0.4) };
+        draw_line(pair[0].x, pair[0].y, pair[1].x, pair[1].y, 1.5, color);
     }
+    // only worth marking if the engine cuts out before the end of the prediction
+    if let Some(i) = prediction.cutoff.filter(|&i| i + 1 < points.len()) {
+        let p = &points[i];
+        draw_circle(p.x, p.y, 4.0, ORANGE);
+        draw_text(&format!("Cut-off {:.1}s", prediction.time_at(i)), p.x + 10.0, p.y - 10.0, 20.0, ORANGE);
+    }
+    draw_path_end(prediction);
 }
 
-fn draw_projected_path(rocket: &Rocket, planet_defs: &[PlanetDef], elapsed: f64) {
-    let path = physics::project_path(rocket, planet_defs, 5.0, 300, elapsed);
-    for (x, y) in path.iter().step_by(15) {
-        draw_circle(*x, *y, 1.5, WHITE);
+fn draw_path_end(prediction: &Prediction) {
+    let points = &prediction.points;
+    if let (Some(end), Some(last)) = (prediction.end, points.last()) {
+        let time = prediction.time_at(points.len() - 1);
+        let (label, color) = match end {
+            PathEnd::Crash => ("Impact", RED),
+            PathEnd::Landing if prediction.stranded => ("Touchdown, too heavy to lift off", ORANGE),
+            PathEnd::Landing => ("Touchdown", GREEN),
+            PathEnd::OutOfBounds => ("Out of bounds", RED),
+        };
+        if end == PathEnd::Landing {
+            draw_circle_lines(last.x, last.y, 6.0, 2.0, color);
+        } else {
+            draw_line(last.x - 6.0, last.y - 6.0, last.x + 6.0, last.y + 6.0, 2.0, color);
+            draw_line(last.x - 6.0, last.y + 6.0, last.x + 6.0, last.y - 6.0, 2.0, color);
+        }
+        draw_text(&format!("{} in {:.1}s", label, time), last.x + 10.0, last.y + 18.0, 20.0, color);
     }
 }
 
-fn draw_hud(elapsed: f64, rocket: &Rocket, planets: &[Planet]) {
-    let speed = (rocket.speed_x * rocket.speed_x + rocket.speed_y * rocket.speed_y).sqrt();
-    let closest_dist = planets.iter().map(|p| {
-        let dx = rocket.x - p.x;
-        let dy = rocket.y - p.y;
-        (dx * dx + dy * dy).sqrt() - p.radius
-    }).reduce(f32::min);
+/// Arrows along the screen edge pointing at the rocket, the goal and any planet
+/// that's out of view, labelled with how far away they are from the rocket.
+fn draw_offscreen_indicators(game: &GameState, planets: &[Planet], screen: Vec2) {
+    let camera = &game.camera;
+    let rocket = vec2(game.level.rocket.x, game.level.rocket.y);
+    let (goal_x, goal_y, goal_radius) = match game.level.level.win_condition {
+        WinCondition::Circle { x, y, radius, .. } | WinCondition::CircleAnySpeed { x, y, radius } => (x, y, radius),
+    };
+    // the rocket's distance is from the middle of the view, everything else from the rocket
+    let mut targets = vec![
+        (rocket, 0.0, ORANGE, format!("Rocket {:.0} px", rocket.distance(camera.center))),
+        (vec2(goal_x, goal_y), goal_radius, GREEN, format!("Goal {:.0} px", (vec2(goal_x, goal_y).distance(rocket) - goal_radius).max(0.0))),
+    ];
+    for planet in planets {
+        let position = vec2(planet.x, planet.y);
+        targets.push((position, planet.radius, LIGHTGRAY, format!("{:.0} px", position.distance(rocket) - planet.radius)));
+    }
+
+    for (position, radius, color, label) in targets {
+        let on_screen = camera.world_to_screen(position, screen);
+        // only count things as off screen once no part of them is visible
+        let nearest = on_screen.clamp(Vec2::ZERO, screen);
+        if on_screen.distance(nearest) <= radius * camera.zoom {
+            continue;
+        }
+        let Some(marker) = camera::edge_indicator(on_screen, screen, 30.0) else { continue };
+        let direction = (on_screen - marker).normalize_or_zero();
+        let side = direction.perp() * 7.0;
+        let tip = marker + direction * 12.0;
+        draw_triangle(tip, marker + side, marker - side, color);
+
+        let dims = measure_text(&label, None, 18, 1.0);
+        // keep the label on screen, on the inside of the arrow
+        let text_pos = (marker - direction * 22.0 - vec2(dims.width / 2.0, -dims.height / 2.0))
+            .clamp(vec2(4.0, dims.height + 4.0), screen - vec2(dims.width + 4.0, 4.0));
+        draw_text(&label, text_pos.x, text_pos.y, 18.0, color);
+    }
+}
+
+/// A scaled-down overview of the whole world in the top-left corner.
+fn draw_minimap(game: &GameState, planets: &[Planet], prediction: Option<&Prediction>, screen: Vec2) {
+    let level = &game.level.level;
+    let max_size = 260.0;
+    let margin = 20.0;
+    let scale = max_size / level.world.x.max(level.world.y);
+    let size = level.world * scale;
+    let to_map = |x: f32, y: f32| (margin + x * scale, margin + y * scale);
+
+    draw_rectangle(margin, margin, size.x, size.y, Color::new(0.0, 0.0, 0.0, 0.7));
+    draw_rectangle_lines(margin, margin, size.x, size.y, 1.0, GRAY);
+
+    let orbit_color = Color::new(1.0, 1.0, 1.0, 0.2);
+    for (def, planet) in level.planets.iter().zip(planets) {
+        if let Some(orbit) = &def.orbit {
+            let (cx, cy) = to_map(def.center_x, def.center_y);
+            draw_circle_lines(cx, cy, orbit.radius * scale, 1.0, orbit_color);
+        }
+        let (px, py) = to_map(planet.x, planet.y);
+        draw_circle(px, py, (planet.radius * scale).max(2.0), LIGHTGRAY);
+    }
+
+    match level.win_condition {
+        WinCondition::Circle { x, y, radius, .. } | WinCondition::CircleAnySpeed { x, y, radius } => {
+            let (gx, gy) = to_map(x, y);
+            draw_circle_lines(gx, gy, (radius * scale).max(3.0), 1.0, GREEN);
+        }
+    }
+
+    if let Some(prediction) = prediction {
+        let mut last = to_map(game.level.rocket.x, game.level.rocket.y);
+        for p in prediction.points.iter().step_by(10) {
+            let next = to_map(p.x, p.y);
+            draw_line(last.0, last.1, next.0, next.1, 1.0, Color::new(1.0, 1.0, 1.0, 0.6));
+            last = next;
+        }
+    }
+
+    // the part of the world currently on screen
+    let camera = &game.camera;
+    let half_view = screen / (2.0 * camera.zoom);
+    let (vx, vy) = to_map(camera.center.x - half_view.x, camera.center.y - half_view.y);
+    let view = (half_view * 2.0 * scale).min(size);
+    draw_rectangle_lines(vx.max(margin), vy.max(margin), view.x, view.y, 1.0, Color::new(0.4, 0.7, 1.0, 0.6));
+
+    let (rx, ry) = to_map(game.level.rocket.x, game.level.rocket.y);
+    draw_circle(rx, ry, 3.0, ORANGE);
+}
+
+fn draw_hud(game: &GameState, elapsed: f64, rocket: &Rocket, planets: &[Planet]) {
     let x = screen_width() - 200.0;
-    draw_text(&format!("FPS: {}", get_fps()), x, screen_height() - 120.0, 24.0, WHITE);
-    if let Some(dist) = closest_dist {
-        draw_text(&format!("Dist: {:.0} px", dist), x, screen_height() - 100.0, 24.0, WHITE);
+    if game.level.level.rotation == RotationMode::Momentum {
+        let sas = if game.sas { " SAS" } else { "" };
+        draw_text(&format!("Spin: {:.0}°/s{}", rocket.angular_velocity, sas), x, screen_height() - 120.0, 24.0, WHITE);
+    }
+    if game.level.level.initial_rocket.rcs_fuel > 0.0 {
+        let firing = rocket.rcs_fuel > 0.0 && (rocket.strafe_x != 0.0 || rocket.strafe_y != 0.0);
+        let rcs_color = if firing { SKYBLUE } else { WHITE };
+        draw_text(&format!("RCS: {:.1}s", rocket.rcs_fuel), x, screen_height() - 100.0, 24.0, rcs_color);
+    }
+    let heat_color = if rocket.heat > 0.75 { RED } else if rocket.heat > 0.0 { ORANGE } else { WHITE };
+    draw_text(&format!("Heat: {:.0}%", rocket.heat * 100.0), x, screen_height() - 80.0, 24.0, heat_color);
+    if physics::stranded(&game.level) {
+        draw_text("LANDED, too heavy to lift off", x, screen_height() - 140.0, 24.0, ORANGE);
+    } else if rocket.landed {
+        draw_text("LANDED", x, screen_height() - 140.0, 24.0, LIME);
+    }
+    draw_text(&format!("FPS: {}", get_fps()), x, screen_height() - 60.0, 24.0, WHITE);
+    if game.show_path {
+        draw_text(&format!("Prediction: {:.0}s", game.path_horizon), x, screen_height() - 160.0, 24.0, GRAY);
+        let burn = match game.burn_plan {
+            Some(seconds) => format!("Burn plan: {:.0}s", seconds),
+            None => "Burn plan: full".to_string(),
+        };
+        draw_text(&burn, x, screen_height() - 180.0, 24.0, GRAY);
+    }
+    let mode = match game.camera.mode {
+        CameraMode::Follow => "Follow",
+        CameraMode::Frame => "Frame",
+        CameraMode::Free => "Free",
+    };
+    draw_text(&format!("Camera: {} x{:.2}", mode, game.camera.zoom), x, screen_height() - 200.0, 24.0, GRAY);
+    let hold = match game.assist {
+        _ if game.autopilot => "Hold: autopilot".to_string(),
+        Some(assist) => format!("Hold: {}", assist.name()),
+        None if game.level.level.assists => "Hold: off".to_string(),
+        None => "Hold: disabled".to_string(),
+    };
+    let hold_color = if game.assist.is_some() || game.autopilot { LIME } else { GRAY };
+    draw_text(&hold, x, screen_height() - 220.0, 24.0, hold_color);
+    let level_id = match (&game.replay, game.level.level.seed) {
+        (Some(replay), _) => Some(format!("Daily {}", replay.date)),
+        (None, Some(seed)) => Some(format!("Seed: {}", seed)),
+        (None, None) => None,
+    };
+    if let Some(level_id) = level_id {
+        draw_text(&level_id, x, screen_height() - 240.0, 24.0, YELLOW);
     }
-    draw_text(&format!("Time: {:.1}s", elapsed), x, screen_height() - 80.0, 24.0, WHITE);
-    draw_text(&format!("Speed: {:.0} px/s", speed), x, screen_height() - 60.0, 24.0, WHITE);
-    draw_text(&format!("Fuel: {:.1}s", rocket.fuel), x, screen_height() - 40.0, 24.0, WHITE);
+    draw_text(&format!("Time: {:.1}s", elapsed), x, screen_height() - 40.0, 24.0, WHITE);
+    let jettisoned = game.level.level.initial_rocket.stages.len() - rocket.stages.len();
+    draw_stages(rocket, jettisoned, x, 30.0);
+    draw_throttle(rocket.throttle, x - 30.0, screen_height() - 160.0, 140.0);
     draw_text(&format!("Accel: {:.1} px/s²", physics::engine_accel(rocket)), x, screen_height() - 20.0, 24.0, WHITE);
+    draw_instruments(game, rocket, planets);
+}
+
+/// The instrument panel in the bottom left: attitude dial, fuel and speed bars,
+/// altitude and the approach to the goal.
+fn draw_instruments(game: &GameState, rocket: &Rocket, planets: &[Planet]) {
+    let left = 20.0;
+    let top = screen_height() - 170.0;
+    draw_rectangle(left, top, 440.0, 150.0, Color::new(0.0, 0.0, 0.0, 0.6));
+    draw_rectangle_lines(left, top, 440.0, 150.0, 1.0, GRAY);
+
+    let nearest = physics::nearest_surface(rocket, planets);
+    draw_attitude(rocket, nearest.map(|(i, _)| &planets[i]), vec2(left + 75.0, top + 75.0), 60.0);
+
+    let x = left + 160.0;
+    let width = 260.0;
+    let jettisoned = game.level.level.initial_rocket.stages.len() - rocket.stages.len();
+    let full = game.level.level.initial_rocket.stages.get(jettisoned).map_or(0.0, |stage| stage.fuel_mass);
+    let fuel = if full > 0.0 { rocket.fuel() / full } else { 0.0 };
+    let fuel_color = if fuel < 0.2 { RED } else { ORANGE };
+    draw_text(&format!("Fuel: {:.1}s burn", physics::burn_time(rocket)), x, top + 22.0, 20.0, WHITE);
+    draw_bar(x, top + 28.0, width, fuel, fuel_color);
+
+    let speed = (rocket.speed_x * rocket.speed_x + rocket.speed_y * rocket.speed_y).sqrt();
+    let max_speed = match game.level.level.win_condition {
+        WinCondition::Circle { max_speed, .. } => Some(max_speed),
+        WinCondition::CircleAnySpeed { .. } => None,
+    };
+    // the scale leaves room past the limit so the marker isn't pinned to the end
+    let full_scale = max_speed.map_or(300.0, |limit| limit * 3.0).max(speed);
+    let speed_color = match max_speed {
+        Some(limit) if speed <= limit => LIME,
+        _ => SKYBLUE,
+    };
+    draw_text(&format!("Speed: {:.0} px/s", speed), x, top + 62.0, 20.0, WHITE);
+    draw_bar(x, top + 68.0, width, speed / full_scale, speed_color);
+    if let Some(limit) = max_speed {
+        let mark = x + width * limit / full_scale;
+        draw_line(mark, top + 64.0, mark, top + 82.0, 2.0, RED);
+    }
+
+    if let Some((_, altitude)) = nearest {
+        draw_text(&format!("Alt: {:.0} px", altitude.max(0.0)), x, top + 106.0, 20.0, WHITE);
+    }
+    let (distance, closing) = physics::goal_approach(rocket, &game.level.level.win_condition);
+    let goal = if distance <= 0.0 {
+        "Goal: inside".to_string()
+    } else {
+        format!("Goal: {:.0} px, closing {:.0} px/s", distance, closing)
+    };
+    let goal_color = if closing > 0.0 || distance <= 0.0 { LIME } else { GRAY };
+    draw_text(&goal, x, top + 130.0, 20.0, goal_color);
+}
+
+fn draw_bar(x: f32, y: f32, width: f32, fill: f32, color: Color) {
+    let height = 10.0;
+    draw_rectangle(x, y, width * fill.clamp(0.0, 1.0), height, color);
+    draw_rectangle_lines(x, y, width, height, 1.0, WHITE);
+}
+
+/// Top-down attitude dial: the rocket's heading as a needle, and prograde and
+/// retrograde markers for the velocity relative to the nearest body.
+fn draw_attitude(rocket: &Rocket, body: Option<&Planet>, center: Vec2, radius: f32) {
+    // angles are clockwise from up, like the rocket's orientation
+    let rim = |angle: f32, r: f32| center + vec2(angle.sin(), -angle.cos()) * r;
+    draw_circle(center.x, center.y, radius, Color::new(0.1, 0.15, 0.25, 0.9));
+    draw_circle_lines(center.x, center.y, radius, 1.0, GRAY);
+
+    let (vx, vy) = match body {
+        Some(planet) => physics::airspeed(rocket, planet),
+        None => (rocket.speed_x, rocket.speed_y),
+    };
+    let speed = (vx * vx + vy * vy).sqrt();
+    if let Some(planet) = body {
+        // the body's direction, so it's clear what the markers are relative to
+        let down = (planet.x - rocket.x).atan2(rocket.y - planet.y);
+        let edge = rim(down, radius);
+        draw_circle(edge.x, edge.y, 5.0, BROWN);
+    }
+    if speed > 0.5 {
+        let prograde = vx.atan2(-vy);
+        let pro = rim(prograde, radius * 0.75);
+        draw_circle_lines(pro.x, pro.y, 6.0, 2.0, LIME);
+        draw_line(pro.x, pro.y - 6.0, pro.x, pro.y - 10.0, 2.0, LIME);
+        let retro = rim(prograde + std::f32::consts::PI, radius * 0.75);
+        draw_line(retro.x - 5.0, retro.y - 5.0, retro.x + 5.0, retro.y + 5.0, 2.0, LIME);
+        draw_line(retro.x - 5.0, retro.y + 5.0, retro.x + 5.0, retro.y - 5.0, 2.0, LIME);
+    }
+    let tip = rim(rocket.orientation.to_radians(), radius * 0.9);
+    draw_line(center.x, center.y, tip.x, tip.y, 3.0, YELLOW);
+    draw_circle(center.x, center.y, 3.0, YELLOW);
+    let text = format!("{:.0} px/s", speed);
+    let dims = measure_text(&text, None, 16, 1.0);
+    draw_text(&text, center.x - dims.width / 2.0, center.y + radius * 0.5, 16.0, WHITE);
+}
+
+/// Lists the remaining delta-v of every stage still attached, numbered from the
+/// rocket's original first stage.
+fn draw_stages(rocket: &Rocket, jettisoned: usize, x: f32, y: f32) {
+    for (i, dv) in physics::stage_delta_v(rocket).iter().enumerate() {
+        let color = if i == 0 { WHITE } else { GRAY };
+        let text = format!("Stage {}: {:.0} px/s", jettisoned + i + 1, dv);
+        draw_text(&text, x, y + i as f32 * 20.0, 24.0, color);
+    }
+}
+
+fn draw_throttle(throttle: f32, x: f32, y: f32, height: f32) {
+    let width = 12.0;
+    draw_rectangle(x, y + height * (1.0 - throttle), width, height * throttle, ORANGE);
+    draw_rectangle_lines(x, y, width, height, 1.0, WHITE);
+    draw_text(&format!("{:.0}%", throttle * 100.0), x - 8.0, y - 6.0, 18.0, WHITE);
+}
+
+/// Shown while an endless level is laid out and checked for a win.
+pub fn draw_generating(seed: u64, attempts: usize) {
+    clear_background(BLACK);
+    let title = format!("Generating level {}", seed);
+    let dims = measure_text(&title, None, 40, 1.0);
+    draw_text(&title, (screen_width() - dims.width) / 2.0, screen_height() / 2.0, 40.0, YELLOW);
+    let text = format!("Checking layout {} can be won...", attempts);
+    let dims = measure_text(&text, None, 24, 1.0);
+    draw_text(&text, (screen_width() - dims.width) / 2.0, screen_height() / 2.0 + 40.0, 24.0, GRAY);
+}
+
+/// Today's daily result, the best days played on this device and what can be done next.
+pub fn draw_daily_result(leaderboard: &Leaderboard, date: &str, can_export: bool, message: Option<&str>) {
+    clear_background(BLACK);
+    let center = |text: &str, y: f32, size: f32, color: Color| {
+        let dims = measure_text(text, None, size as u16, 1.0);
+        draw_text(text, (screen_width() - dims.width) / 2.0, y, size, color);
+    };
+    center(&format!("Daily challenge {}", date), 80.0, 40.0, YELLOW);
+    let today = leaderboard.on(date);
+    if let Some(result) = today {
+        center(&result.description(), 140.0, 28.0, if result.won { LIME } else { ORANGE });
+    }
+
+    center("Best days", 220.0, 30.0, WHITE);
+    let x = screen_width() / 2.0 - 200.0;
+    for (i, result) in leaderboard.ranked().iter().enumerate() {
+        let y = 270.0 + i as f32 * 32.0;
+        let color = if Some(*result) == today { YELLOW } else { GRAY };
+        let outcome = if result.won { format!("{:.1}s", result.time) } else { "lost".to_string() };
+        draw_text(&format!("{}.", i + 1), x, y, 26.0, color);
+        draw_text(&result.date, x + 50.0, y, 26.0, color);
+        draw_text(&result.score.to_string(), x + 230.0, y, 26.0, color);
+        draw_text(&outcome, x + 330.0, y, 26.0, color);
+    }
+
+    if let Some(message) = message {
+        center(message, screen_height() - 90.0, 24.0, SKYBLUE);
+    }
+    let footer = if can_export { "E to export the replay, Escape to return" } else { "Press Escape to return" };
+    center(footer, screen_height() - 40.0, 24.0, GRAY);
 }
 
 pub fn draw_controls() {
@@ -115,10 +782,28 @@ pub fn draw_controls() {
 
     let controls = [
         ("A / D", "Rotate left / right"),
-        ("Z", "Engine on"),
-        ("X", "Engine off"),
+        ("T", "Toggle SAS (momentum levels)"),
+        ("Q / E", "RCS strafe left / right"),
+        ("W / S", "RCS forward / back"),
+        ("Z", "Full throttle"),
+        ("X", "Cut throttle"),
+        ("Shift / Ctrl", "Throttle up / down"),
+        ("Right trigger", "Throttle (gamepad)"),
+        ("Space", "Jettison stage"),
+        ("1 - 5", "Hold pro / retro / radial in / out / goal"),
+        ("I", "Show a solution from here"),
+        ("J", "Autopilot flies a solution"),
+        ("R", "Restart attempt"),
         ("H", "Toggle HUD"),
         ("P", "Toggle trajectory path"),
+        ("M", "Toggle minimap"),
+        ("O", "Toggle orbits and ghost planets"),
+        ("G", "Toggle gravity field"),
+        ("L", "Toggle trails of earlier attempts"),
+        ("[ / ]", "Shorter / longer prediction"),
+        (", / .", "Shorter / longer planned burn"),
+        ("C", "Camera: follow / frame / free"),
+        ("Wheel / Drag", "Zoom / pan the view"),
         ("Escape", "Back to menu"),
     ];
 
@@ -0,0 +1,41 @@
+//! Analog throttle from a gamepad's right trigger. Macroquad doesn't read
+//! gamepads, so this goes through gilrs; the web build has none and always
+//! reads as an untouched trigger.
+
+/// The right trigger of whichever gamepad last moved it.
+pub struct Gamepad {
+    #[cfg(not(target_arch = "wasm32"))]
+    gilrs: Option<gilrs::Gilrs>, // None when the platform's gamepad support wouldn't start
+}
+
+impl Gamepad {
+    pub fn open() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        let gilrs = match gilrs::Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            // a platform without gamepads still hands back one that never sees any
+            Err(gilrs::Error::NotImplemented(gilrs)) => Some(gilrs),
+            Err(_) => None,
+        };
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            gilrs,
+        }
+    }
+
+    /// How far the trigger is pulled, 0 to 1, if it moved since the last call.
+    /// Needs calling every frame, as the pad's events queue up until then.
+    pub fn trigger(&mut self) -> Option<f32> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(gilrs) = &mut self.gilrs {
+            let mut pulled = None;
+            while let Some(event) = gilrs.next_event() {
+                if let gilrs::EventType::ButtonChanged(gilrs::Button::RightTrigger2, value, _) = event.event {
+                    pulled = Some(value);
+                }
+            }
+            return pulled;
+        }
+        None
+    }
+}
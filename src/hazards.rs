@@ -0,0 +1,447 @@
+use std::collections::HashMap;
+use crate::rng::Rng;
+use crate::state::{Orbit, Planet};
+
+/// Cell size of the asteroid broad phase grid, a few times the largest rock.
+pub const CELL_SIZE: f32 = 64.0;
+
+/// Small rocks that destroy the rocket on contact but are too light to pull on it.
+#[derive(Clone)]
+pub enum HazardDef {
+    /// Rocks scattered from a seed along an annulus, all circling its center
+    Belt {
+        center_x: f32,
+        center_y: f32,
+        inner_radius: f32,
+        outer_radius: f32,
+        count: usize,
+        speed: f32, // radians per second at the inner edge, slower further out
+        seed: u64,
+    },
+    /// A rock drifting in a straight line
+    Drift { x: f32, y: f32, speed_x: f32, speed_y: f32, radius: f32 },
+    /// A rock that bounces off planets
+    Tumble { x: f32, y: f32, speed_x: f32, speed_y: f32, radius: f32 },
+}
+
+#[derive(Clone)]
+pub enum Motion {
+    Orbit { center_x: f32, center_y: f32, orbit: Orbit },
+    Drift,
+    Tumble,
+}
+
+#[derive(Clone)]
+pub struct Asteroid {
+    pub x: f32,
+    pub y: f32,
+    pub speed_x: f32,
+    pub speed_y: f32,
+    pub radius: f32,
+    pub angle: f32, // degrees, only used for drawing
+    pub spin: f32,  // degrees per second
+    pub motion: Motion,
+}
+
+pub fn spawn(defs: &[HazardDef]) -> Vec<Asteroid> {
+    let mut asteroids = Vec::new();
+    for def in defs {
+        match def {
+            HazardDef::Belt { center_x, center_y, inner_radius, outer_radius, count, speed, seed } => {
+                let mut rng = Rng::new(*seed);
+                for _ in 0..*count {
+                    let radius = rng.range(*inner_radius, *outer_radius);
+                    let orbit = Orbit {
+                        radius,
+                        // Kepler's third law: angular speed falls with distance^1.5
+                        speed: speed * (inner_radius / radius).powf(1.5),
+                        initial_angle: rng.range(0.0, std::f32::consts::TAU),
+                    };
+                    let (dx, dy) = orbit.offset_at(0.0);
+                    let (speed_x, speed_y) = orbit.velocity_at(0.0);
+                    asteroids.push(Asteroid {
+                        x: center_x + dx,
+                        y: center_y + dy,
+                        speed_x,
+                        speed_y,
+                        radius: rng.range(3.0, 8.0),
+                        angle: rng.range(0.0, 360.0),
+                        spin: rng.range(-90.0, 90.0),
+                        motion: Motion::Orbit { center_x: *center_x, center_y: *center_y, orbit },
+                    });
+                }
+            }
+            HazardDef::Drift { x, y, speed_x, speed_y, radius } => {
+                asteroids.push(Asteroid {
+                    x: *x,
+                    y: *y,
+                    speed_x: *speed_x,
+                    speed_y: *speed_y,
+                    radius: *radius,
+                    angle: 0.0,
+                    spin: 20.0,
+                    motion: Motion::Drift,
+                });
+            }
+            HazardDef::Tumble { x, y, speed_x, speed_y, radius } => {
+                asteroids.push(Asteroid {
+                    x: *x,
+                    y: *y,
+                    speed_x: *speed_x,
+                    speed_y: *speed_y,
+                    radius: *radius,
+                    angle: 0.0,
+                    spin: 120.0,
+                    motion: Motion::Tumble,
+                });
+            }
+        }
+    }
+    asteroids
+}
+
+/// Advances all asteroids by `dt`, ending up at `time` seconds into the level, and
+/// refreshes their grid to match.
+pub fn step(asteroids: &mut [Asteroid], grid: &mut SpatialGrid, planets: &[Planet], time: f64, dt: f32) {
+    for asteroid in asteroids.iter_mut() {
+        move_asteroid(asteroid, planets, time, dt);
+    }
+    grid.rebuild(asteroids);
+}
+
+fn move_asteroid(asteroid: &mut Asteroid, planets: &[Planet], time: f64, dt: f32) {
+    asteroid.angle = (asteroid.angle + asteroid.spin * dt).rem_euclid(360.0);
+    match &asteroid.motion {
+        Motion::Orbit { center_x, center_y, orbit } => {
+            let (dx, dy) = orbit.offset_at(time);
+            asteroid.x = center_x + dx;
+            asteroid.y = center_y + dy;
+        }
+        Motion::Drift => {
+            asteroid.x += asteroid.speed_x * dt;
+            asteroid.y += asteroid.speed_y * dt;
+        }
+        Motion::Tumble => {
+            asteroid.x += asteroid.speed_x * dt;
+            asteroid.y += asteroid.speed_y * dt;
+            for planet in planets {
+                bounce_off_planet(asteroid, planet);
+            }
+        }
+    }
+}
+
+fn bounce_off_planet(asteroid: &mut Asteroid, planet: &Planet) {
+    let dx = asteroid.x - planet.x;
+    let dy = asteroid.y - planet.y;
+    let dist = (dx * dx + dy * dy).sqrt().max(f32::EPSILON);
+    let contact = planet.radius + asteroid.radius;
+    if dist >= contact {
+        return;
+    }
+    let (nx, ny) = (dx / dist, dy / dist);
+    asteroid.x = planet.x + nx * contact;
+    asteroid.y = planet.y + ny * contact;
+    // reflect the velocity relative to the (possibly orbiting) planet
+    let vx = asteroid.speed_x - planet.speed_x;
+    let vy = asteroid.speed_y - planet.speed_y;
+    let towards = vx * nx + vy * ny;
+    if towards < 0.0 {
+        asteroid.speed_x = planet.speed_x + vx - 2.0 * towards * nx;
+        asteroid.speed_y = planet.speed_y + vy - 2.0 * towards * ny;
+        asteroid.spin = -asteroid.spin;
+    }
+}
+
+/// Broad phase for rocket/asteroid collisions: buckets asteroids by grid cell so a
+/// query only looks at the rocks near the rocket. Kept alongside the asteroids and
+/// rebuilt in place as they move, so the buckets' memory is reused.
+#[derive(Clone)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    max_radius: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialGrid {
+    pub fn build(asteroids: &[Asteroid], cell_size: f32) -> Self {
+        let mut grid = Self { cell_size, max_radius: 0.0, cells: HashMap::new() };
+        grid.rebuild(asteroids);
+        grid
+    }
+
+    /// Buckets the asteroids where they are now.
+    pub fn rebuild(&mut self, asteroids: &[Asteroid]) {
+        // cells left empty since the last rebuild are dropped, the others emptied for reuse
+        self.cells.retain(|_, cell| {
+            let used = !cell.is_empty();
+            cell.clear();
+            used
+        });
+        self.max_radius = 0.0;
+        for (i, asteroid) in asteroids.iter().enumerate() {
+            let cell = ((asteroid.x / self.cell_size).floor() as i32, (asteroid.y / self.cell_size).floor() as i32);
+            self.cells.entry(cell).or_default().push(i);
+            self.max_radius = self.max_radius.max(asteroid.radius);
+        }
+    }
+
+    /// Indices of all asteroids whose center is in a cell that a rock touching
+    /// the point could be in.
+    pub fn near(&self, x: f32, y: f32) -> impl Iterator<Item = usize> + '_ {
+        let min_x = ((x - self.max_radius) / self.cell_size).floor() as i32;
+        let max_x = ((x + self.max_radius) / self.cell_size).floor() as i32;
+        let min_y = ((y - self.max_radius) / self.cell_size).floor() as i32;
+        let max_y = ((y + self.max_radius) / self.cell_size).floor() as i32;
+        (min_x..=max_x)
+            .flat_map(move |cx| (min_y..=max_y).map(move |cy| (cx, cy)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+}
+
+pub fn hits_asteroid(x: f32, y: f32, asteroids: &[Asteroid], grid: &SpatialGrid) -> bool {
+    grid.near(x, y).any(|i| {
+        let dx = x - asteroids[i].x;
+        let dy = y - asteroids[i].y;
+        dx * dx + dy * dy < asteroids[i].radius * asteroids[i].radius
+    })
+}
+
+/// The asteroids as a path prediction sees them, asked about one point per step.
+/// Belt rocks keep to fixed orbits, so they're never moved: each belt is kept
+/// sorted by orbit radius and only the rocks whose orbit passes the point get
+/// placed. The few loose rocks are moved along and checked one by one.
+pub struct Forecast {
+    rings: Vec<Ring>,
+    loose: Vec<Asteroid>,
+}
+
+/// Orbiting rocks around one center.
+struct Ring {
+    center_x: f32,
+    center_y: f32,
+    max_radius: f32,          // largest rock, how far off its orbit a rock reaches
+    rocks: Vec<(Orbit, f32)>, // orbit and rock radius, by orbit radius
+}
+
+impl Forecast {
+    pub fn new(asteroids: &[Asteroid]) -> Self {
+        let mut rings: Vec<Ring> = Vec::new();
+        let mut loose = Vec::new();
+        for asteroid in asteroids {
+            let Motion::Orbit { center_x, center_y, orbit } = &asteroid.motion else {
+                loose.push(asteroid.clone());
+                continue;
+            };
+            let ring = match rings.iter().position(|ring| ring.center_x == *center_x && ring.center_y == *center_y) {
+                Some(i) => &mut rings[i],
+                None => {
+                    rings.push(Ring { center_x: *center_x, center_y: *center_y, max_radius: 0.0, rocks: Vec::new() });
+                    rings.last_mut().unwrap()
+                }
+            };
+            ring.max_radius = ring.max_radius.max(asteroid.radius);
+            ring.rocks.push((orbit.clone(), asteroid.radius));
+        }
+        for ring in &mut rings {
+            ring.rocks.sort_by(|a, b| a.0.radius.total_cmp(&b.0.radius));
+        }
+        Self { rings, loose }
+    }
+
+    /// Moves the loose rocks on by `dt`, ending up at `time` seconds into the level.
+    pub fn step(&mut self, planets: &[Planet], time: f64, dt: f32) {
+        for asteroid in &mut self.loose {
+            move_asteroid(asteroid, planets, time, dt);
+        }
+    }
+
+    /// Whether a rock covers the point at `time`, which the loose rocks have been
+    /// stepped to.
+    pub fn hits(&self, x: f32, y: f32, time: f64) -> bool {
+        let covers = |(rock_x, rock_y): (f32, f32), radius: f32| {
+            let (dx, dy) = (x - rock_x, y - rock_y);
+            dx * dx + dy * dy < radius * radius
+        };
+        self.loose.iter().any(|rock| covers((rock.x, rock.y), rock.radius))
+            || self.rings.iter().any(|ring| {
+                ring.passing(x, y).iter().any(|(orbit, radius)| {
+                    let (dx, dy) = orbit.offset_at(time);
+                    covers((ring.center_x + dx, ring.center_y + dy), *radius)
+                })
+            })
+    }
+}
+
+impl Ring {
+    /// The rocks whose orbit comes close enough to the point for them to cover it.
+    fn passing(&self, x: f32, y: f32) -> &[(Orbit, f32)] {
+        let distance = (x - self.center_x).hypot(y - self.center_y);
+        let first = self.rocks.partition_point(|(orbit, _)| orbit.radius < distance - self.max_radius);
+        let last = self.rocks.partition_point(|(orbit, _)| orbit.radius <= distance + self.max_radius);
+        &self.rocks[first..last.max(first)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_rock(x: f32, y: f32, radius: f32) -> Asteroid {
+        Asteroid { x, y, speed_x: 0.0, speed_y: 0.0, radius, angle: 0.0, spin: 0.0, motion: Motion::Drift }
+    }
+
+    fn make_planet(x: f32, y: f32) -> Planet {
+        Planet { x, y, speed_x: 0.0, speed_y: 0.0, radius: 30.0, density: 1.0, image: 0, rotation: 0.0, atmosphere: None }
+    }
+
+    fn make_belt(seed: u64) -> HazardDef {
+        HazardDef::Belt {
+            center_x: 0.0,
+            center_y: 0.0,
+            inner_radius: 100.0,
+            outer_radius: 200.0,
+            count: 50,
+            speed: 0.5,
+            seed,
+        }
+    }
+
+    #[test]
+    fn belt_spawns_within_annulus() {
+        let asteroids = spawn(&[make_belt(1)]);
+        assert_eq!(asteroids.len(), 50);
+        for a in &asteroids {
+            let dist = (a.x * a.x + a.y * a.y).sqrt();
+            assert!((99.9..200.1).contains(&dist));
+        }
+    }
+
+    #[test]
+    fn belt_is_deterministic_per_seed() {
+        let a = spawn(&[make_belt(3)]);
+        let b = spawn(&[make_belt(3)]);
+        let c = spawn(&[make_belt(4)]);
+        assert!(a.iter().zip(&b).all(|(a, b)| a.x == b.x && a.y == b.y));
+        assert!(a.iter().zip(&c).any(|(a, c)| a.x != c.x));
+    }
+
+    #[test]
+    fn belt_rocks_follow_their_orbit() {
+        let mut asteroids = spawn(&[make_belt(5)]);
+        let Motion::Orbit { orbit, .. } = asteroids[0].motion.clone() else { panic!("belt rock should orbit") };
+        let mut grid = SpatialGrid::build(&asteroids, CELL_SIZE);
+        step(&mut asteroids, &mut grid, &[], 2.0, 2.0);
+        let (dx, dy) = orbit.offset_at(2.0);
+        assert!((asteroids[0].x - dx).abs() < 0.001);
+        assert!((asteroids[0].y - dy).abs() < 0.001);
+    }
+
+    #[test]
+    fn drifting_rock_moves_in_straight_line() {
+        let mut asteroids = spawn(&[HazardDef::Drift { x: 0.0, y: 0.0, speed_x: 10.0, speed_y: -5.0, radius: 5.0 }]);
+        let mut grid = SpatialGrid::build(&asteroids, CELL_SIZE);
+        step(&mut asteroids, &mut grid, &[make_planet(0.0, 20.0)], 2.0, 2.0);
+        assert_eq!(asteroids[0].x, 20.0);
+        assert_eq!(asteroids[0].y, -10.0);
+    }
+
+    #[test]
+    fn tumbling_rock_bounces_off_planet() {
+        let mut asteroids = spawn(&[HazardDef::Tumble { x: -40.0, y: 0.0, speed_x: 100.0, speed_y: 0.0, radius: 5.0 }]);
+        let mut grid = SpatialGrid::build(&asteroids, CELL_SIZE);
+        step(&mut asteroids, &mut grid, &[make_planet(0.0, 0.0)], 0.1, 0.1);
+        assert!(asteroids[0].speed_x < 0.0);
+        assert!(asteroids[0].x <= -35.0);
+    }
+
+    #[test]
+    fn grid_finds_nearby_rock() {
+        let asteroids = vec![make_rock(63.0, 10.0, 5.0)];
+        let grid = SpatialGrid::build(&asteroids, 64.0);
+        // the rock reaches across the cell boundary at x = 64
+        assert!(hits_asteroid(66.0, 10.0, &asteroids, &grid));
+    }
+
+    #[test]
+    fn grid_skips_distant_rocks() {
+        let asteroids = vec![make_rock(0.0, 0.0, 5.0), make_rock(500.0, 500.0, 5.0)];
+        let grid = SpatialGrid::build(&asteroids, 64.0);
+        assert_eq!(grid.near(500.0, 500.0).collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn grid_handles_negative_coordinates() {
+        let asteroids = vec![make_rock(-10.0, -10.0, 5.0)];
+        let grid = SpatialGrid::build(&asteroids, 64.0);
+        assert!(hits_asteroid(-12.0, -8.0, &asteroids, &grid));
+        assert!(!hits_asteroid(10.0, 10.0, &asteroids, &grid));
+    }
+
+    #[test]
+    fn step_keeps_grid_in_step_with_rocks() {
+        let mut asteroids = spawn(&[HazardDef::Drift { x: 0.0, y: 0.0, speed_x: 100.0, speed_y: 0.0, radius: 5.0 }]);
+        let mut grid = SpatialGrid::build(&asteroids, CELL_SIZE);
+        assert!(hits_asteroid(0.0, 0.0, &asteroids, &grid));
+        step(&mut asteroids, &mut grid, &[], 2.0, 2.0);
+        assert!(!hits_asteroid(0.0, 0.0, &asteroids, &grid));
+        assert!(hits_asteroid(200.0, 0.0, &asteroids, &grid));
+        // the cell the rock left is dropped on the next rebuild
+        grid.rebuild(&asteroids);
+        assert_eq!(grid.cells.len(), 1);
+    }
+
+    #[test]
+    fn forecast_matches_stepped_rocks() {
+        let defs = [make_belt(9), HazardDef::Tumble { x: -300.0, y: 0.0, speed_x: 60.0, speed_y: 0.0, radius: 6.0 }];
+        let mut asteroids = spawn(&defs);
+        let mut grid = SpatialGrid::build(&asteroids, CELL_SIZE);
+        let mut forecast = Forecast::new(&asteroids);
+        let planets = [make_planet(-150.0, 0.0)];
+        let mut rng = Rng::new(12);
+        for i in 1..=60 {
+            let time = i as f64 * 0.1;
+            step(&mut asteroids, &mut grid, &planets, time, 0.1);
+            forecast.step(&planets, time, 0.1);
+            for _ in 0..50 {
+                let (x, y) = (rng.range(-320.0, 220.0), rng.range(-220.0, 220.0));
+                assert_eq!(forecast.hits(x, y, time), hits_asteroid(x, y, &asteroids, &grid));
+            }
+            for rock in &asteroids {
+                assert!(forecast.hits(rock.x, rock.y, time));
+            }
+        }
+    }
+
+    #[test]
+    fn forecast_only_places_rocks_near_the_point() {
+        let belt = HazardDef::Belt { center_x: 0.0, center_y: 0.0, inner_radius: 100.0, outer_radius: 200.0, count: 1000, speed: 0.5, seed: 2 };
+        let asteroids = spawn(&[belt]);
+        let forecast = Forecast::new(&asteroids);
+        let ring = &forecast.rings[0];
+        // well inside or outside the belt there's nothing to place
+        assert!(ring.passing(0.0, 50.0).is_empty());
+        assert!(ring.passing(400.0, 0.0).is_empty());
+        // inside it, only the rocks within a rock's size of the point's orbit
+        let passing = ring.passing(150.0, 0.0);
+        assert!(!passing.is_empty() && passing.len() < 200, "{}", passing.len());
+        assert!(passing.iter().all(|(orbit, _)| (orbit.radius - 150.0).abs() <= ring.max_radius));
+    }
+
+    #[test]
+    fn grid_matches_brute_force() {
+        let asteroids = spawn(&[make_belt(9)]);
+        let grid = SpatialGrid::build(&asteroids, 32.0);
+        let mut rng = Rng::new(11);
+        for _ in 0..500 {
+            let (x, y) = (rng.range(-220.0, 220.0), rng.range(-220.0, 220.0));
+            let brute = asteroids.iter().any(|a| {
+                let (dx, dy) = (x - a.x, y - a.y);
+                dx * dx + dy * dy < a.radius * a.radius
+            });
+            assert_eq!(hits_asteroid(x, y, &asteroids, &grid), brute);
+        }
+    }
+}
@@ -1,20 +1,34 @@
 use macroquad::prelude::*;
 
+/// Number of planet images, `assets/planets/planet00.png` onwards.
+pub const PLANET_IMAGES: usize = 10;
+
 pub struct Images {
-    pub bg_texture: Texture2D,
+    pub backdrop_texture: Texture2D,
+    pub star_textures: Vec<Texture2D>, // parallax layers, farthest first
     pub planet_textures: Vec<Texture2D>,
+    pub rocket_atlas: Texture2D, // one frame per skin, see `skins::FRAME_SIZE`
 }
 
 impl Images {
     pub async fn load() -> Self {
         let mut planet_textures = Vec::new();
-        for i in 0..10 {
+        for i in 0..PLANET_IMAGES {
             let path = format!("assets/planets/planet{:02}.png", i);
             planet_textures.push(load_texture(&path).await.unwrap());
         }
+        let mut star_textures = Vec::new();
+        for i in 1..=2 {
+            let path = format!("assets/backgrounds/spr_stars{:02}.png", i);
+            star_textures.push(load_texture(&path).await.unwrap());
+        }
+        let rocket_atlas = load_texture("assets/rockets/rockets.png").await.unwrap();
+        rocket_atlas.set_filter(FilterMode::Nearest);
         Self {
-            bg_texture: load_texture("assets/backgrounds/spr_stars01.png").await.unwrap(),
+            backdrop_texture: load_texture("assets/backgrounds/space_backdrop.png").await.unwrap(),
+            star_textures,
             planet_textures,
+            rocket_atlas,
         }
     }
 }
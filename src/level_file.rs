@@ -0,0 +1,294 @@
+//! Levels kept in text files, so designers can try one out with the command
+//! line tools without touching `Level::all()`.
+//!
+//! The first line is `gravioli level`, then one item per line. Blank lines and
+//! anything after a `#` are ignored:
+//!
+//! ```text
+//! gravioli level
+//! name Moon Hop
+//! world 1920 1080                         # width height, the default
+//! rotation instant                        # or momentum
+//! bounds open                             # or wrap, lose, or wall RESTITUTION, around the world
+//! rocket 300 540 0 0 90                   # x y speed_x speed_y orientation [rcs_fuel]
+//! stage 0.5 0.4 10 40                     # dry_mass fuel_mass thrust isp, in firing order
+//! planet 960 540 40 1.0 2 5               # x y radius density image spin
+//! orbit 300 0.4 0                         # radius speed angle, for the planet or canister above
+//! atmosphere 30 0.02 10                   # thickness density scale_height, for the planet above
+//! canister 800 300 0.1                    # x y fuel_mass
+//! depot 0 1.57 0.05                       # planet angle rate
+//! belt 960 540 200 260 30 0.1 7           # x y inner_radius outer_radius count speed seed
+//! drift 500 500 20 -10 12                 # x y speed_x speed_y radius
+//! tumble 500 500 20 -10 12                # x y speed_x speed_y radius
+//! goal 1600 540 60 100                    # x y radius [max_speed], any speed without one
+//! star 0                                  # planet that lights the others
+//! hard                                    # no auto-hold assists
+//! par 12.5                                # seconds for a gold medal, from gravioli-solve
+//! ```
+
+use macroquad::prelude::*;
+use crate::hazards::HazardDef;
+use crate::state::{Atmosphere, Bounds, FuelCanister, FuelDepot, Level, Orbit, PlanetDef, Rocket, RotationMode, Stage, WinCondition};
+
+/// What the last `planet` or `canister` line placed, which the `orbit` and
+/// `atmosphere` lines after it belong to.
+enum Placed {
+    Planet,
+    Canister,
+}
+
+/// A whole number of at least zero, for indices and counts.
+fn whole(value: f32) -> Option<usize> {
+    (value >= 0.0 && value.fract() == 0.0).then_some(value as usize)
+}
+
+/// Reads a level from the text of a level file.
+pub fn parse(text: &str) -> Result<Level, String> {
+    let mut lines = text.lines();
+    if lines.next().map(str::trim) != Some("gravioli level") {
+        return Err("not a gravioli level".to_string());
+    }
+    let mut name = None;
+    let mut world = vec2(1920.0, 1080.0);
+    let mut bounds = "open".to_string();
+    let mut rotation = RotationMode::Instant;
+    let mut rocket = None;
+    let mut stages = Vec::new();
+    let mut planets: Vec<PlanetDef> = Vec::new();
+    let mut canisters: Vec<FuelCanister> = Vec::new();
+    let mut depots = Vec::new();
+    let mut hazards = Vec::new();
+    let mut win_condition = None;
+    let mut star = None;
+    let mut assists = true;
+    let mut par = None;
+    let mut last_placed = None;
+
+    for (number, line) in lines.enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let bad_line = || format!("line {} doesn't make sense: {}", number + 2, line);
+        let (item, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let values: Option<Vec<f32>> = rest.split_whitespace().map(|value| value.parse().ok()).collect();
+        let values = || values.clone().ok_or_else(bad_line);
+        match item {
+            "name" if !rest.trim().is_empty() => name = Some(rest.trim().to_string()),
+            "world" => {
+                let [width, height] = values()?[..] else { return Err(bad_line()) };
+                world = vec2(width, height);
+            }
+            "rotation" => {
+                rotation = match rest.trim() {
+                    "instant" => RotationMode::Instant,
+                    "momentum" => RotationMode::Momentum,
+                    _ => return Err(bad_line()),
+                }
+            }
+            "bounds" => bounds = rest.trim().to_string(),
+            "rocket" => {
+                let (x, y, speed_x, speed_y, orientation, rcs_fuel) = match values()?[..] {
+                    [x, y, speed_x, speed_y, orientation] => (x, y, speed_x, speed_y, orientation, 0.0),
+                    [x, y, speed_x, speed_y, orientation, rcs_fuel] => (x, y, speed_x, speed_y, orientation, rcs_fuel),
+                    _ => return Err(bad_line()),
+                };
+                rocket = Some(Rocket {
+                    x,
+                    y,
+                    speed_x,
+                    speed_y,
+                    orientation,
+                    landed: false,
+                    throttle: 0.0,
+                    stages: vec![],
+                    heat: 0.0,
+                    angular_velocity: 0.0,
+                    rcs_fuel,
+                    strafe_x: 0.0,
+                    strafe_y: 0.0,
+                });
+            }
+            "stage" => {
+                let [dry_mass, fuel_mass, thrust, isp] = values()?[..] else { return Err(bad_line()) };
+                stages.push(Stage { dry_mass, fuel_mass, thrust, isp });
+            }
+            "planet" => {
+                let [center_x, center_y, radius, density, image, spin] = values()?[..] else { return Err(bad_line()) };
+                let image = whole(image).ok_or_else(bad_line)?;
+                planets.push(PlanetDef { center_x, center_y, radius, density, image, spin, orbit: None, atmosphere: None });
+                last_placed = Some(Placed::Planet);
+            }
+            "orbit" => {
+                let [radius, speed, initial_angle] = values()?[..] else { return Err(bad_line()) };
+                let orbit = Some(Orbit { radius, speed, initial_angle });
+                match last_placed {
+                    Some(Placed::Planet) => planets.last_mut().ok_or_else(bad_line)?.orbit = orbit,
+                    Some(Placed::Canister) => canisters.last_mut().ok_or_else(bad_line)?.orbit = orbit,
+                    _ => return Err(bad_line()),
+                }
+            }
+            "atmosphere" => {
+                let [thickness, density, scale_height] = values()?[..] else { return Err(bad_line()) };
+                let Some(Placed::Planet) = last_placed else { return Err(bad_line()) };
+                let planet = planets.last_mut().ok_or_else(bad_line)?;
+                planet.atmosphere = Some(Atmosphere { thickness, density, scale_height });
+            }
+            "canister" => {
+                let [center_x, center_y, fuel_mass] = values()?[..] else { return Err(bad_line()) };
+                canisters.push(FuelCanister { center_x, center_y, orbit: None, fuel_mass });
+                last_placed = Some(Placed::Canister);
+            }
+            "depot" => {
+                let [planet, angle, rate] = values()?[..] else { return Err(bad_line()) };
+                let planet = whole(planet).ok_or_else(bad_line)?;
+                depots.push(FuelDepot { planet, angle, rate });
+            }
+            "belt" => {
+                let [center_x, center_y, inner_radius, outer_radius, count, speed, _] = values()?[..] else {
+                    return Err(bad_line());
+                };
+                let count = whole(count).ok_or_else(bad_line)?;
+                // read on its own, as a float can't hold every seed
+                let seed = rest.split_whitespace().nth(6).and_then(|seed| seed.parse().ok()).ok_or_else(bad_line)?;
+                hazards.push(HazardDef::Belt { center_x, center_y, inner_radius, outer_radius, count, speed, seed });
+            }
+            "drift" => {
+                let [x, y, speed_x, speed_y, radius] = values()?[..] else { return Err(bad_line()) };
+                hazards.push(HazardDef::Drift { x, y, speed_x, speed_y, radius });
+            }
+            "tumble" => {
+                let [x, y, speed_x, speed_y, radius] = values()?[..] else { return Err(bad_line()) };
+                hazards.push(HazardDef::Tumble { x, y, speed_x, speed_y, radius });
+            }
+            "goal" => {
+                win_condition = Some(match values()?[..] {
+                    [x, y, radius] => WinCondition::CircleAnySpeed { x, y, radius },
+                    [x, y, radius, max_speed] => WinCondition::Circle { x, y, radius, max_speed },
+                    _ => return Err(bad_line()),
+                });
+            }
+            "star" => star = Some(rest.trim().parse().map_err(|_| bad_line())?),
+            "hard" if rest.is_empty() => assists = false,
+            "par" => {
+                let [seconds] = values()?[..] else { return Err(bad_line()) };
+                par = Some(seconds);
+            }
+            _ => return Err(bad_line()),
+        }
+    }
+
+    let area = Rect::new(0.0, 0.0, world.x, world.y);
+    let bounds = match bounds.split_whitespace().collect::<Vec<_>>()[..] {
+        ["open"] => Bounds::Open,
+        ["wrap"] => Bounds::Wrap(area),
+        ["lose"] => Bounds::Lose(area),
+        ["wall", restitution] => {
+            let restitution = restitution.parse().map_err(|_| format!("bad wall restitution: {}", restitution))?;
+            Bounds::Wall { rect: area, restitution }
+        }
+        _ => return Err(format!("unknown bounds: {}", bounds)),
+    };
+    let mut rocket = rocket.ok_or("the level has no rocket")?;
+    if stages.is_empty() {
+        return Err("the rocket has no stages".to_string());
+    }
+    rocket.stages = stages;
+    Ok(Level {
+        // a level file is loaded once by a tool, so its name can live forever
+        name: Box::leak(name.ok_or("the level has no name")?.into_boxed_str()),
+        planets,
+        initial_rocket: rocket,
+        win_condition: win_condition.ok_or("the level has no goal")?,
+        rotation,
+        canisters,
+        depots,
+        hazards,
+        bounds,
+        world,
+        star,
+        assists,
+        seed: None,
+        par,
+    })
+}
+
+/// Reads the level file at `path`.
+pub fn load(path: &str) -> Result<Level, String> {
+    let text = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+    parse(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::check;
+
+    #[test]
+    fn example_level_loads_and_passes_the_checks() {
+        let level = parse(include_str!("../levels/example.txt")).unwrap();
+        assert_eq!(level.name, "Moon Hop");
+        assert_eq!(level.planets.len(), 2);
+        assert!(level.planets[1].orbit.is_some());
+        assert_eq!(level.initial_rocket.fuel(), 0.4);
+        assert!(matches!(level.win_condition, WinCondition::Circle { max_speed, .. } if max_speed == 100.0));
+        assert_eq!(check::problems(&level), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reads_every_item() {
+        let text = "gravioli level
+            name Everything
+            world 3000 2000
+            rotation momentum
+            bounds wall 0.5
+            rocket 100 100 5 0 45 8
+            stage 0.5 0.2 10 40
+            stage 0.3 0.1 5 60
+            planet 1000 1000 50 1.2 3 10
+            atmosphere 20 0.01 8
+            canister 500 500 0.1
+            orbit 200 0.3 1.0
+            depot 0 3.14 0.05
+            belt 1000 1000 200 300 20 0.1 18446744073709551615
+            drift 10 20 1 2 8
+            tumble 30 40 3 4 9
+            goal 2500 1500 80
+            star 0
+            hard
+            par 42.5
+        ";
+        let level = parse(text).unwrap();
+        assert_eq!(level.world, vec2(3000.0, 2000.0));
+        assert!(level.rotation == RotationMode::Momentum);
+        assert!(matches!(level.bounds, Bounds::Wall { restitution, rect } if restitution == 0.5 && rect.w == 3000.0));
+        assert_eq!(level.initial_rocket.rcs_fuel, 8.0);
+        assert_eq!(level.initial_rocket.stages.len(), 2);
+        assert!(level.planets[0].atmosphere.is_some());
+        assert!(level.planets[0].orbit.is_none());
+        assert!(level.canisters[0].orbit.is_some());
+        assert_eq!(level.depots[0].planet, 0);
+        assert_eq!(level.hazards.len(), 3);
+        assert!(matches!(level.hazards[0], HazardDef::Belt { count: 20, seed: u64::MAX, .. }));
+        assert!(matches!(level.win_condition, WinCondition::CircleAnySpeed { .. }));
+        assert_eq!(level.star, Some(0));
+        assert!(!level.assists);
+        assert_eq!(level.par, Some(42.5));
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        assert!(parse("name Nope").is_err());
+        let missing_goal = "gravioli level\nname Nope\nrocket 0 0 0 0 0\nstage 1 1 1 1\n";
+        assert_eq!(parse(missing_goal).err().unwrap(), "the level has no goal");
+        let bad = "gravioli level\nname Nope\nplanet 1 2 three 1 0 0\n";
+        assert_eq!(parse(bad).err().unwrap(), "line 3 doesn't make sense: planet 1 2 three 1 0 0");
+        assert!(parse("gravioli level\norbit 1 2 3\n").is_err());
+        for line in ["depot -1 0 0.1", "planet 1 2 3 1 2.5 0", "belt 0 0 10 20 -3 0.1 1", "belt 0 0 10 20 3 0.1 1.5"] {
+            assert!(parse(&format!("gravioli level\nname Nope\n{}\n", line)).is_err(), "{}", line);
+        }
+        // an atmosphere goes round the planet above, not a canister in between
+        let atmosphere_after_canister = "gravioli level\nname Nope\nplanet 0 0 10 1 0 0\ncanister 5 5 0.1\natmosphere 5 0.1 2\n";
+        assert_eq!(parse(atmosphere_after_canister).err().unwrap(), "line 5 doesn't make sense: atmosphere 5 0.1 2");
+    }
+}
@@ -0,0 +1,23 @@
+//! The game itself, shared by the `gravioli` binary and the command line tools
+//! in `src/bin`.
+
+pub mod camera;
+pub mod check;
+pub mod cli;
+pub mod controls;
+pub mod daily;
+pub mod drawing;
+pub mod gamepad;
+pub mod hazards;
+pub mod images;
+pub mod level_file;
+pub mod menu;
+pub mod particles;
+pub mod physics;
+pub mod procgen;
+pub mod progress;
+pub mod rng;
+pub mod skins;
+pub mod solver;
+pub mod sound;
+pub mod state;
@@ -1,12 +1,6 @@
 use macroquad::prelude::*;
 
-mod controls;
-mod drawing;
-mod images;
-mod menu;
-mod physics;
-mod sound;
-mod state;
+use gravioli::{controls, daily, drawing, gamepad, images, menu, physics, procgen, progress, skins, sound, state};
 
 fn window_conf() -> Conf {
     Conf {
@@ -20,10 +14,39 @@ fn window_conf() -> Conf {
 #[derive(Clone)]
 enum Screen {
     Menu,
-    Playing(state::GameState),
+    Generating { generator: Box<procgen::Generator>, daily: Option<String> }, // date of a daily challenge
+    Playing(Box<state::GameState>),
+    DailyResult { date: String, replay: Option<Box<daily::Replay>>, message: Option<String> },
     Controls,
 }
 
+/// Seconds of each frame given to the solver while it searches for a hint.
+const SEARCH_TIME_PER_FRAME: f64 = 0.01;
+
+/// Advances any solver search by a frame's worth, returning whether it's still going.
+fn searching(game: &mut state::GameState) -> bool {
+    let Some(solver) = &mut game.solver else { return false };
+    let start = get_time();
+    while !solver.finished() && get_time() - start < SEARCH_TIME_PER_FRAME {
+        solver.step(1);
+    }
+    !solver.finished()
+}
+
+/// Seconds of each frame spent building an endless level.
+const GENERATE_TIME_PER_FRAME: f64 = 0.02;
+
+/// Works on an endless level for a frame's worth, returning it once it's ready.
+fn generate(generator: &mut procgen::Generator) -> Option<state::Level> {
+    let start = get_time();
+    while get_time() - start < GENERATE_TIME_PER_FRAME {
+        if let Some(level) = generator.step(10) {
+            return Some(level);
+        }
+    }
+    None
+}
+
 #[macroquad::main(window_conf)]
 async fn main() {
     info!("Starting Gravioli");
@@ -34,15 +57,38 @@ async fn main() {
     next_frame().await;
     next_frame().await;
     let levels = state::Level::all();
+    let skins = skins::Skin::all();
+    let mut progress = progress::Progress::load();
+    let mut leaderboard = daily::Leaderboard::load();
     let mut menu = menu::Menu::new(&levels);
     let mut screen = Screen::Menu;
+    let mut difficulty = procgen::Difficulty::all()[0]; // of the endless levels
+    let mut gamepad = gamepad::Gamepad::open();
 
     loop {
+        // read on every screen, so the pad's events don't pile up outside a level
+        let trigger = gamepad.trigger();
         match &mut screen {
             Screen::Menu => {
-                match menu.update() {
+                match menu.update(&mut progress) {
                     Some(menu::MenuChoice::Play(i)) if i < levels.len() => {
-                        screen = Screen::Playing(state::GameState::new(levels[i].clone()));
+                        let skin = skins.get(progress.skin).unwrap_or(&skins[0]).clone();
+                        screen = Screen::Playing(Box::new(state::GameState::new(levels[i].clone(), skin)));
+                    }
+                    Some(menu::MenuChoice::Endless { seed, difficulty: chosen }) => {
+                        difficulty = chosen;
+                        let generator = Box::new(procgen::Generator::new(seed, difficulty));
+                        screen = Screen::Generating { generator, daily: None };
+                    }
+                    Some(menu::MenuChoice::Daily) => {
+                        let date = daily::today();
+                        screen = if leaderboard.on(&date).is_some() {
+                            let message = Some("Today's challenge is flown, come back tomorrow".to_string());
+                            Screen::DailyResult { date, replay: None, message }
+                        } else {
+                            let generator = Box::new(procgen::Generator::new(daily::seed(&date), daily::difficulty()));
+                            Screen::Generating { generator, daily: Some(date) }
+                        };
                     }
                     Some(menu::MenuChoice::Controls) => {
                         screen = Screen::Controls;
@@ -51,27 +97,103 @@ async fn main() {
                     _ => {}
                 }
             }
+            Screen::Generating { generator, daily } => {
+                if is_key_pressed(KeyCode::Escape) {
+                    screen = Screen::Menu;
+                } else if let Some(mut level) = generate(generator) {
+                    let skin = skins.get(progress.skin).unwrap_or(&skins[0]).clone();
+                    let replay = daily.as_deref().map(daily::Replay::new);
+                    if let Some(date) = daily {
+                        level.name = "Daily challenge";
+                        leaderboard.start(date);
+                    }
+                    let mut game = state::GameState::new(level, skin);
+                    game.replay = replay;
+                    screen = Screen::Playing(Box::new(game));
+                } else {
+                    drawing::draw_generating(generator.seed(), generator.attempts());
+                }
+            }
             Screen::Playing(game) => {
                 let dt = get_frame_time();
                 let mut return_to_menu = false;
-                if controls::handle_input(&mut game.level.rocket, dt, &mut game.show_hud, &mut game.show_path) {
-                    return_to_menu = true;
+                let mut next_seed = None;
+                let mut daily_over = false;
+                if controls::handle_input(game, dt, trigger) {
+                    match &mut game.replay {
+                        // leaving a daily challenge early gives it up
+                        Some(replay) => {
+                            if replay.result.is_none() {
+                                leaderboard.set(replay.finish(&game.level, false));
+                            }
+                            daily_over = true;
+                        }
+                        None => return_to_menu = true,
+                    }
+                } else if searching(game) {
+                    // the world waits while the solver looks for a hint
+                    drawing::draw(game, &images);
                 } else {
                     sounds.update(&game.level.rocket);
-                    match physics::update(game, dt) {
-                        Some(physics::PhysicsEvent::Collision) => {
+                    let event = controls::update(game, dt);
+                    if let Some(replay) = &mut game.replay {
+                        replay.record(&game.level);
+                    }
+                    match event {
+                        Some(physics::PhysicsEvent::Collision)
+                        | Some(physics::PhysicsEvent::Overheat)
+                        | Some(physics::PhysicsEvent::OutOfBounds) => {
                             sounds.play_explosion();
+                            if let Some(replay) = &mut game.replay {
+                                leaderboard.set(replay.finish(&game.level, false));
+                            }
                         }
                         Some(physics::PhysicsEvent::Win) => {
                             sounds.play_level_complete();
-                            return_to_menu = true;
+                            if let Some(replay) = &mut game.replay {
+                                leaderboard.set(replay.finish(&game.level, true));
+                            } else {
+                                match game.level.level.seed {
+                                    // endless mode carries on with the next seed
+                                    Some(seed) => next_seed = Some(seed + 1),
+                                    None => {
+                                        // a run the autopilot flew doesn't earn a medal
+                                        let time = (!game.autopilot).then(|| game.level.attempt_time());
+                                        progress.complete_level(game.level.level.name, time);
+                                        return_to_menu = true;
+                                    }
+                                }
+                            }
                         }
                         None => {}
                     }
-                    drawing::draw(&game.level.level.planets, &game.level.rocket, &game.level.level.win_condition, &images, game.level.elapsed(), game.show_hud, game.show_path);
+                    game.camera.update(&game.level, dt, vec2(screen_width(), screen_height()));
+                    drawing::draw(game, &images);
+                }
+                // a lost daily run is over once its crash has played out
+                if game.replay.as_ref().is_some_and(|replay| replay.result.is_some()) && game.crash.is_none() {
+                    daily_over = true;
                 }
                 if return_to_menu {
                     screen = Screen::Menu;
+                } else if let Some(seed) = next_seed {
+                    let generator = Box::new(procgen::Generator::new(seed, difficulty));
+                    screen = Screen::Generating { generator, daily: None };
+                } else if daily_over && let Some(replay) = game.replay.take() {
+                    let date = replay.date.clone();
+                    screen = Screen::DailyResult { date, replay: Some(Box::new(replay)), message: None };
+                }
+            }
+            Screen::DailyResult { date, replay, message } => {
+                drawing::draw_daily_result(&leaderboard, date, replay.is_some(), message.as_deref());
+                if is_key_pressed(KeyCode::E) && let Some(replay) = replay {
+                    *message = Some(match replay.export() {
+                        Ok(path) => format!("Saved the replay to {}", path),
+                        Err(error) => format!("Couldn't save the replay: {}", error),
+                    });
+                }
+                if is_key_pressed(KeyCode::Escape) {
+                    screen = Screen::Menu;
                 }
             }
             Screen::Controls => {
@@ -81,6 +203,10 @@ async fn main() {
                 }
             }
         }
+        if !matches!(screen, Screen::Menu) {
+            // typed characters are only read by the menu's seed entry
+            clear_input_queue();
+        }
         next_frame().await;
     }
 }
@@ -1,8 +1,24 @@
 use macroquad::prelude::*;
+use crate::procgen::Difficulty;
+use crate::progress::{Medal, Progress};
+use crate::skins::Skin;
 use crate::state::Level;
 
 pub enum MenuChoice {
     Play(usize),
+    Endless { seed: u64, difficulty: Difficulty },
+    Daily,
+    Controls,
+    Exit,
+}
+
+/// A line of the menu.
+#[derive(Clone, Copy, PartialEq)]
+enum Entry {
+    Level(usize, &'static str, Option<f32>), // index into `Level::all()`, the level's name and par time
+    Endless,
+    Daily,
+    Rocket,
     Controls,
     Exit,
 }
@@ -10,61 +26,173 @@ pub enum MenuChoice {
 #[derive(Clone)]
 pub struct Menu {
     selected: usize,
-    options: Vec<&'static str>,
+    entries: Vec<Entry>,
+    skins: Vec<Skin>,
+    difficulties: Vec<Difficulty>,
+    difficulty: usize,  // index into `difficulties` for the endless mode
+    seed_input: String, // seed typed in to replay a shared level, empty for a random one
 }
 
 impl Menu {
-    pub fn new(levels: &Vec<Level>) -> Self {
-        let level_names: Vec<&str> = levels.iter().map(|l| l.name).collect();
-
-        let mut options = level_names;
-        options.push("Controls");
-        options.push("Exit");
-        Self { selected: 0, options }
+    pub fn new(levels: &[Level]) -> Self {
+        let mut entries: Vec<Entry> = levels.iter().enumerate().map(|(i, l)| Entry::Level(i, l.name, l.par)).collect();
+        entries.extend([Entry::Endless, Entry::Daily, Entry::Rocket, Entry::Controls, Entry::Exit]);
+        Self {
+            selected: 0,
+            entries,
+            skins: Skin::all(),
+            difficulties: Difficulty::all(),
+            difficulty: 0,
+            seed_input: String::new(),
+        }
     }
 
-    pub fn update(&mut self) -> Option<MenuChoice> {
-        let choice = self.handle_input();
+    pub fn update(&mut self, progress: &mut Progress) -> Option<MenuChoice> {
+        let choice = self.handle_input(progress);
         if choice.is_none() {
-            self.draw();
+            self.draw(progress);
         }
         choice
     }
 
-    fn handle_input(&mut self) -> Option<MenuChoice> {
+    fn selected_entry(&self) -> Entry {
+        self.entries[self.selected]
+    }
+
+    /// Left and Right pick the difficulty, digits and Backspace edit the seed.
+    fn handle_endless_input(&mut self) -> Option<MenuChoice> {
+        let count = self.difficulties.len();
+        if is_key_pressed(KeyCode::Left) {
+            self.difficulty = (self.difficulty + count - 1) % count;
+        }
+        if is_key_pressed(KeyCode::Right) {
+            self.difficulty = (self.difficulty + 1) % count;
+        }
+        let mut typed: Vec<char> = std::iter::from_fn(get_char_pressed).collect();
+        // the queue hands back the newest character first
+        typed.reverse();
+        for c in typed {
+            // long enough for any seed the menu hands out, short enough to fit a u64
+            if c.is_ascii_digit() && self.seed_input.len() < 9 {
+                self.seed_input.push(c);
+            }
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            self.seed_input.pop();
+        }
+        if !is_key_pressed(KeyCode::Enter) {
+            return None;
+        }
+        let seed = match self.seed_input.parse() {
+            Ok(seed) => seed,
+            // six digits are easy to read out to someone
+            Err(_) => (macroquad::miniquad::date::now() * 1000.0) as u64 % 1_000_000,
+        };
+        Some(MenuChoice::Endless { seed, difficulty: self.difficulties[self.difficulty] })
+    }
+
+    /// Steps through the unlocked skins, saving the choice.
+    fn cycle_skin(&self, progress: &mut Progress, step: isize) {
+        let count = self.skins.len() as isize;
+        let mut skin = progress.skin as isize;
+        for _ in 0..count {
+            skin = (skin + step).rem_euclid(count);
+            if self.skins[skin as usize].unlocked(progress) {
+                progress.select_skin(skin as usize);
+                return;
+            }
+        }
+    }
+
+    fn handle_input(&mut self, progress: &mut Progress) -> Option<MenuChoice> {
         if is_key_pressed(KeyCode::Up) && self.selected > 0 {
             self.selected -= 1;
         }
-        if is_key_pressed(KeyCode::Down) && self.selected < self.options.len() - 1 {
+        if is_key_pressed(KeyCode::Down) && self.selected < self.entries.len() - 1 {
             self.selected += 1;
         }
-        if is_key_pressed(KeyCode::Enter) {
-            let last = self.options.len() - 1;
-            return match self.selected {
-                i if i == last => Some(MenuChoice::Exit),
-                i if i == last - 1 => Some(MenuChoice::Controls),
-                i => Some(MenuChoice::Play(i)),
-            };
+        if self.selected_entry() == Entry::Endless {
+            return self.handle_endless_input();
+        }
+        clear_input_queue();
+        if self.selected_entry() == Entry::Rocket {
+            if is_key_pressed(KeyCode::Left) {
+                self.cycle_skin(progress, -1);
+            }
+            if is_key_pressed(KeyCode::Right) || is_key_pressed(KeyCode::Enter) {
+                self.cycle_skin(progress, 1);
+            }
+            return None;
+        }
+        if !is_key_pressed(KeyCode::Enter) {
+            return None;
+        }
+        match self.selected_entry() {
+            Entry::Level(i, ..) => Some(MenuChoice::Play(i)),
+            Entry::Daily => Some(MenuChoice::Daily),
+            Entry::Controls => Some(MenuChoice::Controls),
+            Entry::Exit => Some(MenuChoice::Exit),
+            // handled above, as they take more than Enter
+            Entry::Endless | Entry::Rocket => None,
         }
-        None
     }
 
-    pub fn draw(&self) {
+    pub fn draw(&self, progress: &Progress) {
         clear_background(BLACK);
         let font_size = 32.0;
         let line_height = 40.0;
-        let start_y = screen_height() / 2.0 - (self.options.len() as f32 * line_height) / 2.0;
+        let start_y = screen_height() / 2.0 - (self.entries.len() as f32 * line_height) / 2.0;
 
-        for (i, option) in self.options.iter().enumerate() {
+        for (i, entry) in self.entries.iter().enumerate() {
             let color = if i == self.selected { YELLOW } else { WHITE };
+            let label = match entry {
+                Entry::Level(_, name, par) => match (progress.best_time(name), par) {
+                    (Some(best), Some(par)) => format!("{} [{}]", name, Medal::for_time(best, *par).name()),
+                    _ => name.to_string(),
+                },
+                Entry::Endless => format!("Endless: < {} >", self.difficulties[self.difficulty].name),
+                Entry::Daily => "Daily challenge".to_string(),
+                Entry::Rocket => format!("Rocket: < {} >", self.skins[progress.skin.min(self.skins.len() - 1)].name),
+                Entry::Controls => "Controls".to_string(),
+                Entry::Exit => "Exit".to_string(),
+            };
             let text = if i == self.selected {
-                format!("> {}", option)
+                format!("> {}", label)
             } else {
-                format!("  {}", option)
+                format!("  {}", label)
             };
             let dims = measure_text(&text, None, font_size as u16, 1.0);
             let x = (screen_width() - dims.width) / 2.0;
             draw_text(&text, x, start_y + i as f32 * line_height, font_size, color);
         }
+
+        let hint = match self.selected_entry() {
+            Entry::Endless if self.seed_input.is_empty() => {
+                "Type a seed to play a shared level, or press Enter for a random one".to_string()
+            }
+            Entry::Endless => format!("Seed: {}", self.seed_input),
+            Entry::Level(_, name, par) => match (progress.best_time(name), par) {
+                (Some(best), Some(par)) => format!("Par {:.1}s, your best {:.1}s", par, best),
+                (None, Some(par)) => format!("Par {:.1}s", par),
+                (Some(best), None) => format!("Your best {:.1}s", best),
+                (None, None) => String::new(),
+            },
+            Entry::Daily => "One attempt a day at a level that's the same for everyone".to_string(),
+            Entry::Rocket => {
+                let locked: Vec<String> = self
+                    .skins
+                    .iter()
+                    .filter(|skin| !skin.unlocked(progress))
+                    .map(|skin| format!("{} ({})", skin.name, skin.requirement()))
+                    .collect();
+                if locked.is_empty() { String::new() } else { format!("Locked: {}", locked.join(", ")) }
+            }
+            _ => String::new(),
+        };
+        if !hint.is_empty() {
+            let dims = measure_text(&hint, None, 22, 1.0);
+            let y = start_y + self.entries.len() as f32 * line_height + 20.0;
+            draw_text(&hint, (screen_width() - dims.width) / 2.0, y, 22.0, GRAY);
+        }
     }
 }
@@ -0,0 +1,340 @@
+use macroquad::prelude::*;
+use crate::rng::Rng;
+use crate::state::Rocket;
+
+/// Upper bound on live particles; past this the oldest slots get recycled.
+const MAX_PARTICLES: usize = 2048;
+
+#[derive(Clone)]
+pub struct Particle {
+    pub x: f32,
+    pub y: f32,
+    pub speed_x: f32,
+    pub speed_y: f32,
+    pub age: f32,
+    pub lifetime: f32,
+    pub size: f32,
+    pub growth: f32, // change in size per second
+    pub color: Color,
+}
+
+impl Particle {
+    pub fn alive(&self) -> bool {
+        self.age < self.lifetime
+    }
+}
+
+/// How a batch of particles leaves its source.
+struct Spray {
+    direction: f32, // radians clockwise from up, like the rocket's orientation
+    spread: f32,    // radians either side of `direction`
+    speed: (f32, f32),
+    lifetime: (f32, f32),
+    size: f32,
+    growth: f32,
+    color: Color,
+}
+
+/// Pool of short-lived visual particles. Dead particles keep their slot and are
+/// handed out again, so once the pool has warmed up nothing gets allocated.
+#[derive(Clone)]
+pub struct Particles {
+    pool: Vec<Particle>,
+    free: Vec<usize>, // slots of dead particles
+    next: usize,      // slot to overwrite when the pool is full
+    rng: Rng,
+}
+
+impl Default for Particles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Particles {
+    pub fn new() -> Self {
+        Self { pool: Vec::new(), free: Vec::new(), next: 0, rng: Rng::new(0) }
+    }
+
+    pub fn alive(&self) -> impl Iterator<Item = &Particle> {
+        self.pool.iter().filter(|p| p.alive())
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for (i, particle) in self.pool.iter_mut().enumerate() {
+            if !particle.alive() {
+                continue;
+            }
+            particle.age += dt;
+            if !particle.alive() {
+                self.free.push(i);
+                continue;
+            }
+            particle.x += particle.speed_x * dt;
+            particle.y += particle.speed_y * dt;
+            particle.size = (particle.size + particle.growth * dt).max(0.0);
+        }
+    }
+
+    fn spawn(&mut self, particle: Particle) {
+        if let Some(slot) = self.free.pop() {
+            self.pool[slot] = particle;
+        } else if self.pool.len() < MAX_PARTICLES {
+            self.pool.push(particle);
+        } else {
+            self.pool[self.next] = particle;
+            self.next = (self.next + 1) % MAX_PARTICLES;
+        }
+    }
+
+    /// Number of particles to emit this frame for an emitter running at `rate` per second.
+    fn count(&mut self, rate: f32, dt: f32) -> usize {
+        (rate * dt + self.rng.next_f32()) as usize
+    }
+
+    /// Emits `count` particles from `x, y`, moving with `base` velocity plus the spray's own.
+    fn spray(&mut self, count: usize, x: f32, y: f32, base: (f32, f32), spray: &Spray) {
+        for _ in 0..count {
+            let angle = spray.direction + self.rng.range(-spray.spread, spray.spread);
+            let speed = self.rng.range(spray.speed.0, spray.speed.1);
+            let lifetime = self.rng.range(spray.lifetime.0, spray.lifetime.1);
+            self.spawn(Particle {
+                x,
+                y,
+                speed_x: base.0 + angle.sin() * speed,
+                speed_y: base.1 - angle.cos() * speed,
+                age: 0.0,
+                lifetime,
+                size: spray.size,
+                growth: spray.growth,
+                color: spray.color,
+            });
+        }
+    }
+
+    /// Flame out of the nozzle, denser and faster the harder the engine is pushed.
+    /// `nozzle` is where the flame starts relative to the rocket when it points up.
+    pub fn exhaust(&mut self, rocket: &Rocket, nozzle: Vec2, dt: f32) {
+        if rocket.throttle <= 0.0 || rocket.fuel() <= 0.0 {
+            return;
+        }
+        let count = self.count(300.0 * rocket.throttle, dt);
+        let backwards = rocket.orientation.to_radians() + std::f32::consts::PI;
+        let speed = 60.0 + 120.0 * rocket.throttle;
+        let base = (rocket.speed_x, rocket.speed_y);
+        let (sin, cos) = rocket.orientation.to_radians().sin_cos();
+        let x = rocket.x + nozzle.x * cos - nozzle.y * sin;
+        let y = rocket.y + nozzle.x * sin + nozzle.y * cos;
+        let flame = Spray {
+            direction: backwards,
+            spread: 0.25,
+            speed: (speed * 0.7, speed),
+            lifetime: (0.2, 0.45),
+            size: 3.0,
+            growth: -4.0,
+            color: ORANGE,
+        };
+        self.spray(count, x, y, base, &flame);
+    }
+
+    /// Small puffs of gas opposite to each RCS thruster that's firing.
+    pub fn rcs(&mut self, rocket: &Rocket, dt: f32) {
+        if rocket.rcs_fuel <= 0.0 {
+            return;
+        }
+        let angle = rocket.orientation.to_radians();
+        let base = (rocket.speed_x, rocket.speed_y);
+        let puff = Color::new(0.9, 0.9, 1.0, 0.8);
+        // the gas leaves against the direction of the push, from halfway up the hull
+        let mid = (rocket.x + angle.sin() * 15.0, rocket.y - angle.cos() * 15.0);
+        let mut pushes = Vec::new();
+        if rocket.strafe_x != 0.0 {
+            pushes.push(angle + std::f32::consts::FRAC_PI_2 * rocket.strafe_x.signum());
+        }
+        if rocket.strafe_y != 0.0 {
+            pushes.push(if rocket.strafe_y > 0.0 { angle } else { angle + std::f32::consts::PI });
+        }
+        for push in pushes {
+            let count = self.count(60.0, dt);
+            let gas = Spray {
+                direction: push + std::f32::consts::PI,
+                spread: 0.3,
+                speed: (30.0, 50.0),
+                lifetime: (0.15, 0.3),
+                size: 2.0,
+                growth: 6.0,
+                color: puff,
+            };
+            self.spray(count, mid.0, mid.1, base, &gas);
+        }
+    }
+
+    /// A burst of fire and smoke where the rocket was destroyed.
+    pub fn explosion(&mut self, x: f32, y: f32, speed_x: f32, speed_y: f32) {
+        // the wreck keeps a little of the rocket's momentum
+        let base = (speed_x * 0.2, speed_y * 0.2);
+        let all_around = std::f32::consts::PI;
+        let fire = Spray {
+            direction: 0.0,
+            spread: all_around,
+            speed: (40.0, 160.0),
+            lifetime: (0.4, 1.0),
+            size: 3.0,
+            growth: -2.0,
+            color: ORANGE,
+        };
+        let flash = Spray {
+            direction: 0.0,
+            spread: all_around,
+            speed: (20.0, 90.0),
+            lifetime: (0.3, 0.6),
+            size: 4.0,
+            growth: -5.0,
+            color: YELLOW,
+        };
+        let smoke = Spray {
+            direction: 0.0,
+            spread: all_around,
+            speed: (10.0, 40.0),
+            lifetime: (1.0, 1.8),
+            size: 4.0,
+            growth: 8.0,
+            color: Color::new(0.5, 0.5, 0.5, 0.6),
+        };
+        self.spray(80, x, y, base, &fire);
+        self.spray(40, x, y, base, &flash);
+        self.spray(40, x, y, base, &smoke);
+    }
+
+    /// Dust kicked up sideways along the surface on touchdown. `normal` points away
+    /// from the planet and `impact` is how fast the rocket came down.
+    pub fn dust(&mut self, x: f32, y: f32, normal: (f32, f32), impact: f32) {
+        let up = normal.0.atan2(-normal.1);
+        let count = 10 + impact as usize;
+        for side in [-1.0, 1.0] {
+            let dust = Spray {
+                direction: up + side * 1.2,
+                spread: 0.3,
+                speed: (15.0, 25.0 + impact),
+                lifetime: (0.5, 1.2),
+                size: 2.0,
+                growth: 5.0,
+                color: Color::new(0.7, 0.6, 0.5, 0.7),
+            };
+            self.spray(count, x, y, (0.0, 0.0), &dust);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::Stage;
+
+    fn make_rocket(throttle: f32) -> Rocket {
+        Rocket {
+            x: 0.0,
+            y: 0.0,
+            speed_x: 0.0,
+            speed_y: 0.0,
+            orientation: 0.0,
+            landed: false,
+            throttle,
+            stages: vec![Stage { dry_mass: 0.5, fuel_mass: 0.5, thrust: 10.0, isp: 40.0 }],
+            heat: 0.0,
+            angular_velocity: 0.0,
+            rcs_fuel: 1.0,
+            strafe_x: 0.0,
+            strafe_y: 0.0,
+        }
+    }
+
+    fn emitted_over_a_second(throttle: f32) -> usize {
+        let mut particles = Particles::new();
+        let rocket = make_rocket(throttle);
+        for _ in 0..60 {
+            particles.exhaust(&rocket, Vec2::ZERO, 1.0 / 60.0);
+        }
+        particles.alive().count()
+    }
+
+    #[test]
+    fn exhaust_scales_with_throttle() {
+        assert_eq!(emitted_over_a_second(0.0), 0);
+        let half = emitted_over_a_second(0.5);
+        let full = emitted_over_a_second(1.0);
+        assert!((120..180).contains(&half));
+        assert!(full > half + 100);
+    }
+
+    #[test]
+    fn exhaust_leaves_behind_the_rocket() {
+        let mut particles = Particles::new();
+        particles.exhaust(&make_rocket(1.0), Vec2::ZERO, 0.1);
+        // pointing up, so the flame goes down
+        assert!(particles.alive().all(|p| p.speed_y > 0.0));
+    }
+
+    #[test]
+    fn exhaust_starts_at_rotated_nozzle() {
+        let mut particles = Particles::new();
+        let mut rocket = make_rocket(1.0);
+        rocket.orientation = 90.0;
+        particles.exhaust(&rocket, vec2(0.0, 4.0), 0.1);
+        // pointing right, so a nozzle below the base ends up to its left
+        assert!(particles.alive().all(|p| (p.x + 4.0).abs() < 0.001 && p.y.abs() < 0.001));
+    }
+
+    #[test]
+    fn particles_die_after_lifetime() {
+        let mut particles = Particles::new();
+        particles.explosion(0.0, 0.0, 0.0, 0.0);
+        assert_eq!(particles.alive().count(), 160);
+        particles.update(0.5);
+        let remaining = particles.alive().count();
+        assert!(remaining > 0 && remaining < 160);
+        particles.update(2.0);
+        assert_eq!(particles.alive().count(), 0);
+    }
+
+    #[test]
+    fn dead_slots_are_reused() {
+        let mut particles = Particles::new();
+        particles.explosion(0.0, 0.0, 0.0, 0.0);
+        particles.update(2.0);
+        particles.explosion(0.0, 0.0, 0.0, 0.0);
+        assert_eq!(particles.pool.len(), 160);
+    }
+
+    #[test]
+    fn pool_never_grows_past_capacity() {
+        let mut particles = Particles::new();
+        for _ in 0..20 {
+            particles.explosion(0.0, 0.0, 0.0, 0.0);
+        }
+        assert_eq!(particles.pool.len(), MAX_PARTICLES);
+        assert_eq!(particles.alive().count(), MAX_PARTICLES);
+    }
+
+    #[test]
+    fn rcs_puffs_only_while_strafing() {
+        let mut particles = Particles::new();
+        let mut rocket = make_rocket(0.0);
+        particles.rcs(&rocket, 1.0);
+        assert_eq!(particles.alive().count(), 0);
+        rocket.strafe_x = 1.0;
+        particles.rcs(&rocket, 1.0);
+        // pushing right blows gas out to the left
+        assert!(particles.alive().count() > 0);
+        assert!(particles.alive().all(|p| p.speed_x < 0.0));
+    }
+
+    #[test]
+    fn landing_dust_spreads_both_ways() {
+        let mut particles = Particles::new();
+        particles.dust(0.0, 0.0, (0.0, -1.0), 10.0);
+        assert!(particles.alive().any(|p| p.speed_x < 0.0));
+        assert!(particles.alive().any(|p| p.speed_x > 0.0));
+        assert!(particles.alive().all(|p| p.speed_y < 0.0));
+    }
+}
@@ -1,28 +1,143 @@
 use macroquad::prelude::*;
-use crate::state::{GameState, Planet, PlanetDef, Rocket, WinCondition};
+use crate::hazards::{self, Asteroid, Forecast};
+use crate::state::{Assist, Bounds, Crash, CrashCause, Debris, FuelDepot, GameState, LevelState, Planet, PlanetDef, Rocket, Stage, TrailPoint, WinCondition};
+
+/// Standard gravity, used to turn a stage's specific impulse into exhaust velocity.
+const G0: f32 = 10.0;
 
 pub enum PhysicsEvent {
     Collision,
+    Overheat,
+    OutOfBounds,
     Win,
 }
 
+/// Advances the game by `dt`: the level's physics plus the particles and the
+/// crash replay that go with it.
 pub fn update(game: &mut GameState, dt: f32) -> Option<PhysicsEvent> {
-    let current_planets = game.level.current_planets();
+    if game.crash.is_some() {
+        update_crash(game, dt);
+        return None;
+    }
+    let rocket = &game.level.rocket;
+    let (was_landed, speed_x, speed_y) = (rocket.landed, rocket.speed_x, rocket.speed_y);
+    game.particles.update(dt);
+    game.particles.exhaust(rocket, game.skin.flame_offset(), dt);
+    game.particles.rcs(rocket, dt);
+    let event = step(&mut game.level, dt);
+    match event {
+        Some(PhysicsEvent::Collision) => crash(game, CrashCause::Collision),
+        Some(PhysicsEvent::Overheat) => crash(game, CrashCause::Overheat),
+        Some(PhysicsEvent::OutOfBounds) => crash(game, CrashCause::OutOfBounds),
+        Some(PhysicsEvent::Win) => {}
+        None if game.level.rocket.landed && !was_landed => {
+            let rocket = &game.level.rocket;
+            let planets = game.level.current_planets();
+            if let Some(planet) = surface_under(rocket.x, rocket.y, &planets) {
+                let (vx, vy) = (speed_x - planet.speed_x, speed_y - planet.speed_y);
+                let (nx, ny) = ((rocket.x - planet.x) / planet.radius, (rocket.y - planet.y) / planet.radius);
+                game.particles.dust(rocket.x, rocket.y, (nx, ny), (vx * vx + vy * vy).sqrt());
+            }
+        }
+        None => {}
+    }
+    event
+}
+
+/// Advances the level's physics by `dt`, without any particles, returning what
+/// ended the attempt if anything did. The solver flies its runs with this.
+pub fn step(level: &mut LevelState, dt: f32) -> Option<PhysicsEvent> {
+    level.time += dt as f64;
+    let elapsed = level.time;
+    let current_planets: Vec<_> = level.level.planets.iter().map(|p| p.planet_at(elapsed)).collect();
     for planet in &current_planets {
-        apply_gravity(&mut game.level.rocket, planet, dt);
+        apply_gravity(&mut level.rocket, planet, dt);
     }
-    apply_engine(&mut game.level.rocket, dt);
-    move_rocket(&mut game.level.rocket, dt);
-    if current_planets.iter().any(|p| check_collision(&game.level.rocket, p)) {
-        game.level.reset_rocket();
+    let density = air_density(&level.rocket, &current_planets);
+    apply_drag(&mut level.rocket, &current_planets, density, dt);
+    apply_heating(&mut level.rocket, &current_planets, density, dt);
+    apply_engine(&mut level.rocket, dt);
+    apply_rcs(&mut level.rocket, dt);
+    move_rocket(&mut level.rocket, dt);
+    rotate_rocket(&mut level.rocket, dt);
+    if !apply_bounds(&mut level.rocket, &level.level.bounds) {
+        return Some(PhysicsEvent::OutOfBounds);
+    }
+    record_trail(level);
+    update_debris(&mut level.debris, &current_planets, dt);
+    hazards::step(&mut level.asteroids, &mut level.grid, &current_planets, elapsed, dt);
+    if hazards::hits_asteroid(level.rocket.x, level.rocket.y, &level.asteroids, &level.grid) {
         return Some(PhysicsEvent::Collision);
     }
-    if check_win(&game.level.rocket, &game.level.level.win_condition) {
+    if level.rocket.heat >= 1.0 {
+        return Some(PhysicsEvent::Overheat);
+    }
+    let rocket = &mut level.rocket;
+    match current_planets.iter().find(|p| check_collision(rocket, p)) {
+        // once landed, the surface holds the rocket up until the engine lifts it off
+        Some(planet) if can_land(rocket, planet) => land(rocket, planet),
+        Some(_) => return Some(PhysicsEvent::Collision),
+        None => rocket.landed = false,
+    }
+    collect_canisters(level, elapsed);
+    refuel_at_depots(level, &current_planets, dt);
+    if check_win(&level.rocket, &level.level.win_condition) {
         return Some(PhysicsEvent::Win);
     }
     None
 }
 
+/// Adds the rocket's position to its trail every few pixels, or whenever the engine
+/// starts or stops, and forgets points older than the trail's length.
+fn record_trail(level: &mut LevelState) {
+    let spacing = 4.0;
+    let max_age = 30.0;
+    let rocket = &level.rocket;
+    let burning = rocket.throttle > 0.0;
+    let due = level.trail.last().is_none_or(|last| {
+        let (dx, dy) = (rocket.x - last.x, rocket.y - last.y);
+        dx * dx + dy * dy >= spacing * spacing || last.burning != burning
+    });
+    if due {
+        let speed = (rocket.speed_x * rocket.speed_x + rocket.speed_y * rocket.speed_y).sqrt();
+        level.trail.push(TrailPoint { x: rocket.x, y: rocket.y, speed, burning, time: level.time });
+    }
+    let expired = level.trail.iter().take_while(|p| level.time - p.time > max_age).count();
+    level.trail.drain(..expired);
+}
+
+/// Ends the attempt where the rocket is. The wreck stays put while the crash
+/// plays out, and the next attempt starts once the player restarts.
+fn crash(game: &mut GameState, cause: CrashCause) {
+    let rocket = &mut game.level.rocket;
+    if cause != CrashCause::OutOfBounds {
+        game.particles.explosion(rocket.x, rocket.y, rocket.speed_x, rocket.speed_y);
+    }
+    rocket.throttle = 0.0;
+    rocket.strafe_x = 0.0;
+    rocket.strafe_y = 0.0;
+    game.crash = Some(Crash { x: rocket.x, y: rocket.y, cause, timer: 0.0 });
+}
+
+/// After a crash the rest of the world carries on in slow motion so the player can
+/// see what went wrong, until they restart or the replay runs out.
+fn update_crash(game: &mut GameState, dt: f32) {
+    let slow_motion = 0.25;
+    let replay_length = 5.0; // real seconds before restarting on its own
+    let Some(crash) = &mut game.crash else { return };
+    crash.timer += dt;
+    if crash.timer >= replay_length {
+        game.restart();
+        return;
+    }
+    let dt = dt * slow_motion;
+    game.level.time += dt as f64;
+    let planets = game.level.current_planets();
+    game.particles.update(dt);
+    update_debris(&mut game.level.debris, &planets, dt);
+    hazards::step(&mut game.level.asteroids, &mut game.level.grid, &planets, game.level.time, dt);
+}
+
 fn check_win(rocket: &Rocket, condition: &WinCondition) -> bool {
     match condition {
         WinCondition::Circle { x, y, radius, max_speed } => {
@@ -30,42 +145,166 @@ fn check_win(rocket: &Rocket, condition: &WinCondition) -> bool {
             let dy = rocket.y - y;
             let in_circle = dx * dx + dy * dy < radius * radius;
             let speed = (rocket.speed_x * rocket.speed_x + rocket.speed_y * rocket.speed_y).sqrt();
-            in_circle && speed < *max_speed && !rocket.engine_on
+            in_circle && speed < *max_speed && rocket.throttle == 0.0
         }
         WinCondition::CircleAnySpeed { x, y, radius } => {
             let dx = rocket.x - x;
             let dy = rocket.y - y;
-            dx * dx + dy * dy < radius * radius && !rocket.engine_on
+            dx * dx + dy * dy < radius * radius && rocket.throttle == 0.0
         }
     }
 }
 
 fn apply_gravity(rocket: &mut Rocket, planet: &Planet, dt: f32) {
-    let dx = planet.x - rocket.x;
-    let dy = planet.y - rocket.y;
+    let (ax, ay) = gravity_accel(rocket.x, rocket.y, planet);
+    rocket.speed_x += ax * dt;
+    rocket.speed_y += ay * dt;
+}
+
+/// Combined pull of all planets on anything at the given point.
+pub fn gravity_field(x: f32, y: f32, planets: &[Planet]) -> (f32, f32) {
+    planets.iter().fold((0.0, 0.0), |(sum_x, sum_y), planet| {
+        let (ax, ay) = gravity_accel(x, y, planet);
+        (sum_x + ax, sum_y + ay)
+    })
+}
+
+fn gravity_accel(x: f32, y: f32, planet: &Planet) -> (f32, f32) {
+    let dx = planet.x - x;
+    let dy = planet.y - y;
     let dist_sq = dx * dx + dy * dy;
     let dist = dist_sq.sqrt();
     let r = planet.radius / 30.0;
-    let gravity = 4000000.0 * r * r * r;
+    let gravity = 4000000.0 * r * r * r * planet.density;
     let accel = gravity / dist_sq;
-    rocket.speed_x += (dx / dist) * accel * dt;
-    rocket.speed_y += (dy / dist) * accel * dt;
+    ((dx / dist) * accel, (dy / dist) * accel)
+}
+
+/// Returns the atmosphere density at the rocket's position, together with the
+/// index of the planet whose atmosphere it is in.
+pub fn air_density(rocket: &Rocket, planets: &[Planet]) -> Option<(usize, f32)> {
+    planets.iter().enumerate().find_map(|(i, planet)| {
+        let atmosphere = planet.atmosphere.as_ref()?;
+        let dx = rocket.x - planet.x;
+        let dy = rocket.y - planet.y;
+        let altitude = (dx * dx + dy * dy).sqrt() - planet.radius;
+        if altitude < 0.0 || altitude > atmosphere.thickness {
+            return None;
+        }
+        Some((i, atmosphere.density * (-altitude / atmosphere.scale_height).exp()))
+    })
+}
+
+/// Speed of the rocket relative to the air of the given planet.
+pub fn airspeed(rocket: &Rocket, planet: &Planet) -> (f32, f32) {
+    (rocket.speed_x - planet.speed_x, rocket.speed_y - planet.speed_y)
+}
+
+fn apply_drag(rocket: &mut Rocket, planets: &[Planet], density: Option<(usize, f32)>, dt: f32) {
+    let Some((i, density)) = density else { return };
+    let (vx, vy) = airspeed(rocket, &planets[i]);
+    let speed = (vx * vx + vy * vy).sqrt();
+    // quadratic drag, integrated implicitly so it can slow the rocket but never reverse it
+    let factor = 1.0 / (1.0 + density * speed * dt);
+    rocket.speed_x = planets[i].speed_x + vx * factor;
+    rocket.speed_y = planets[i].speed_y + vy * factor;
+}
+
+fn apply_heating(rocket: &mut Rocket, planets: &[Planet], density: Option<(usize, f32)>, dt: f32) {
+    let heating_rate = 0.00003;
+    let cooling_rate = 0.2; // heat per second
+    if let Some((i, density)) = density {
+        let (vx, vy) = airspeed(rocket, &planets[i]);
+        let speed = (vx * vx + vy * vy).sqrt();
+        rocket.heat += heating_rate * density * speed * speed * speed * dt;
+    }
+    rocket.heat = (rocket.heat - cooling_rate * dt).max(0.0);
 }
 
 fn apply_engine(rocket: &mut Rocket, dt: f32) {
-    if rocket.engine_on && rocket.fuel > 0.0 {
-        apply_thrust(rocket, dt);
-        rocket.fuel = (rocket.fuel - dt).max(0.0);
-        if rocket.fuel == 0.0 {
-            rocket.engine_on = false;
+    if rocket.throttle > 0.0 && rocket.fuel() > 0.0 {
+        // partial throttle gives a proportional share of full thrust and fuel flow
+        apply_thrust(rocket, rocket.throttle * dt);
+        let stage = &mut rocket.stages[0];
+        stage.fuel_mass = (stage.fuel_mass - mass_flow(stage) * rocket.throttle * dt).max(0.0);
+        if stage.fuel_mass == 0.0 {
+            rocket.throttle = 0.0;
         }
     }
 }
 
+/// Fuel mass burnt per second at full throttle.
+fn mass_flow(stage: &Stage) -> f32 {
+    stage.thrust / (stage.isp * G0)
+}
+
+pub fn rocket_mass(rocket: &Rocket) -> f32 {
+    rocket.stages.iter().map(|stage| stage.dry_mass + stage.fuel_mass).sum()
+}
+
 pub fn engine_accel(rocket: &Rocket) -> f32 {
-    let force = 10.0;
-    let mass = 0.5 + 0.5 * (rocket.fuel / 20.0);
-    force / mass
+    match rocket.stages.first() {
+        Some(stage) => stage.thrust / rocket_mass(rocket),
+        None => 0.0,
+    }
+}
+
+/// Seconds of full-throttle burn left in the active stage.
+pub fn burn_time(rocket: &Rocket) -> f32 {
+    rocket.stages.first().map_or(0.0, |stage| stage.fuel_mass / mass_flow(stage))
+}
+
+/// Remaining delta-v of each stage from the rocket equation, in firing order.
+/// Each stage has to push the mass of all the stages above it.
+pub fn stage_delta_v(rocket: &Rocket) -> Vec<f32> {
+    (0..rocket.stages.len())
+        .map(|i| {
+            let stage = &rocket.stages[i];
+            let full: f32 = rocket.stages[i..].iter().map(|s| s.dry_mass + s.fuel_mass).sum();
+            let empty = full - stage.fuel_mass;
+            stage.isp * G0 * (full / empty).ln()
+        })
+        .collect()
+}
+
+/// Drops the active stage, leaving it behind as debris. The last stage can't be jettisoned.
+pub fn jettison_stage(level: &mut LevelState) -> bool {
+    let rocket = &mut level.rocket;
+    if rocket.stages.len() < 2 {
+        return false;
+    }
+    rocket.stages.remove(0);
+    rocket.throttle = 0.0;
+    // the spent stage is pushed backwards by the separation
+    let separation_speed = 15.0;
+    let angle = rocket.orientation.to_radians();
+    level.debris.push(Debris {
+        x: rocket.x,
+        y: rocket.y,
+        speed_x: rocket.speed_x - angle.sin() * separation_speed,
+        speed_y: rocket.speed_y + angle.cos() * separation_speed,
+        orientation: rocket.orientation,
+    });
+    true
+}
+
+fn update_debris(debris: &mut Vec<Debris>, planets: &[Planet], dt: f32) {
+    for piece in debris.iter_mut() {
+        for planet in planets {
+            let (ax, ay) = gravity_accel(piece.x, piece.y, planet);
+            piece.speed_x += ax * dt;
+            piece.speed_y += ay * dt;
+        }
+        piece.x += piece.speed_x * dt;
+        piece.y += piece.speed_y * dt;
+    }
+    debris.retain(|piece| {
+        !planets.iter().any(|p| {
+            let dx = piece.x - p.x;
+            let dy = piece.y - p.y;
+            dx * dx + dy * dy < p.radius * p.radius
+        })
+    });
 }
 
 pub fn apply_thrust(rocket: &mut Rocket, dt: f32) {
@@ -75,26 +314,396 @@ pub fn apply_thrust(rocket: &mut Rocket, dt: f32) {
     rocket.speed_y -= angle.cos() * accel * dt;
 }
 
+/// Fires the RCS translation thrusters along the rocket's own axes.
+pub fn apply_rcs(rocket: &mut Rocket, dt: f32) {
+    let rcs_accel = 3.0;
+    let thrusters = rocket.strafe_x.abs() + rocket.strafe_y.abs();
+    if thrusters == 0.0 || rocket.rcs_fuel <= 0.0 {
+        return;
+    }
+    let angle = rocket.orientation.to_radians();
+    // forward is (sin, -cos) and right is (cos, sin) in screen coordinates
+    let ax = rocket.strafe_y * angle.sin() + rocket.strafe_x * angle.cos();
+    let ay = -rocket.strafe_y * angle.cos() + rocket.strafe_x * angle.sin();
+    rocket.speed_x += ax * rcs_accel * dt;
+    rocket.speed_y += ay * rcs_accel * dt;
+    rocket.rcs_fuel = (rocket.rcs_fuel - thrusters * dt).max(0.0);
+}
+
 pub fn move_rocket(rocket: &mut Rocket, dt: f32) {
     rocket.x += rocket.speed_x * dt;
     rocket.y += rocket.speed_y * dt;
 }
 
-pub fn project_path(rocket: &Rocket, planet_defs: &[PlanetDef], duration: f32, steps: usize, start_time: f64) -> Vec<(f32, f32)> {
+pub fn rotate_rocket(rocket: &mut Rocket, dt: f32) {
+    rocket.orientation = (rocket.orientation + rocket.angular_velocity * dt).rem_euclid(360.0);
+}
+
+fn can_land(rocket: &Rocket, planet: &Planet) -> bool {
+    let landing_speed = 30.0;
+    let dx = rocket.speed_x - planet.speed_x;
+    let dy = rocket.speed_y - planet.speed_y;
+    dx * dx + dy * dy < landing_speed * landing_speed
+}
+
+/// Whether the engine pulls harder than the planet's surface gravity, so a rocket
+/// landed there isn't stuck for good.
+pub fn can_take_off(rocket: &Rocket, planet: &Planet) -> bool {
+    let (ax, ay) = gravity_accel(planet.x + planet.radius, planet.y, planet);
+    engine_accel(rocket) > (ax * ax + ay * ay).sqrt()
+}
+
+/// The planet whose surface is closest to the point, the one a landed rocket sits on.
+fn surface_under(x: f32, y: f32, planets: &[Planet]) -> Option<&Planet> {
+    let height = |planet: &Planet| (vec2(x - planet.x, y - planet.y).length() - planet.radius).abs();
+    planets.iter().min_by(|a, b| height(a).total_cmp(&height(b)))
+}
+
+/// Whether the rocket sits on a planet its engine is too weak to lift it off.
+pub fn stranded(level: &LevelState) -> bool {
+    let rocket = &level.rocket;
+    let planets = level.current_planets();
+    rocket.landed && surface_under(rocket.x, rocket.y, &planets).is_some_and(|planet| !can_take_off(rocket, planet))
+}
+
+/// Puts the rocket on the planet's surface, moving along with it.
+fn land(rocket: &mut Rocket, planet: &Planet) {
+    let dx = rocket.x - planet.x;
+    let dy = rocket.y - planet.y;
+    let dist = (dx * dx + dy * dy).sqrt().max(f32::EPSILON);
+    rocket.x = planet.x + dx / dist * planet.radius;
+    rocket.y = planet.y + dy / dist * planet.radius;
+    rocket.speed_x = planet.speed_x;
+    rocket.speed_y = planet.speed_y;
+    rocket.landed = true;
+}
+
+fn collect_canisters(level: &mut LevelState, elapsed: f64) {
+    let pickup_radius = 20.0;
+    let rocket = &mut level.rocket;
+    for (canister, collected) in level.level.canisters.iter().zip(level.collected.iter_mut()) {
+        let (x, y) = canister.position_at(elapsed);
+        let dx = rocket.x - x;
+        let dy = rocket.y - y;
+        if !*collected && dx * dx + dy * dy < pickup_radius * pickup_radius {
+            *collected = true;
+            if let Some(stage) = rocket.stages.first_mut() {
+                stage.fuel_mass += canister.fuel_mass;
+            }
+        }
+    }
+}
+
+/// Tops up the active stage, up to what it started with, while landed on a depot.
+fn refuel_at_depots(level: &mut LevelState, planets: &[Planet], dt: f32) {
+    let pad_radius = 20.0;
+    let rocket = &mut level.rocket;
+    if !rocket.landed || rocket.stages.is_empty() {
+        return;
+    }
+    let jettisoned = level.level.initial_rocket.stages.len() - rocket.stages.len();
+    let capacity = level.level.initial_rocket.stages[jettisoned].fuel_mass;
+    for depot in &level.level.depots {
+        let (x, y) = depot_position(depot, &planets[depot.planet]);
+        let dx = rocket.x - x;
+        let dy = rocket.y - y;
+        if dx * dx + dy * dy < pad_radius * pad_radius {
+            let stage = &mut rocket.stages[0];
+            stage.fuel_mass = (stage.fuel_mass + depot.rate * dt).min(capacity.max(stage.fuel_mass));
+        }
+    }
+}
+
+pub fn depot_position(depot: &FuelDepot, planet: &Planet) -> (f32, f32) {
+    (planet.x + planet.radius * depot.angle.cos(), planet.y + planet.radius * depot.angle.sin())
+}
+
+/// Wraps or bounces the rocket at the edge of the play area. Returns false if the
+/// rocket has left an area that can't be left.
+pub fn apply_bounds(rocket: &mut Rocket, bounds: &Bounds) -> bool {
+    match bounds {
+        Bounds::Open => true,
+        Bounds::Wrap(rect) => {
+            rocket.x = rect.x + (rocket.x - rect.x).rem_euclid(rect.w);
+            rocket.y = rect.y + (rocket.y - rect.y).rem_euclid(rect.h);
+            true
+        }
+        Bounds::Wall { rect, restitution } => {
+            if rocket.x < rect.left() || rocket.x > rect.right() {
+                rocket.x = rocket.x.clamp(rect.left(), rect.right());
+                rocket.speed_x = -rocket.speed_x * restitution;
+            }
+            if rocket.y < rect.top() || rocket.y > rect.bottom() {
+                rocket.y = rocket.y.clamp(rect.top(), rect.bottom());
+                rocket.speed_y = -rocket.speed_y * restitution;
+            }
+            true
+        }
+        Bounds::Lose(rect) => rect.contains(Vec2::new(rocket.x, rocket.y)),
+    }
+}
+
+#[derive(Clone)]
+pub struct PathPoint {
+    pub x: f32,
+    pub y: f32,
+    pub speed_x: f32,
+    pub speed_y: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PathEnd {
+    Crash,
+    Landing,
+    OutOfBounds,
+}
+
+/// How far ahead to predict a path and how the engine is flown along it.
+#[derive(Clone, Copy)]
+pub struct PathRequest {
+    pub burn: f32,       // seconds the engine keeps its current throttle
+    pub duration: f32,   // seconds of flight to predict
+    pub steps: usize,    // points along the path, one per time step
+    pub start_time: f64, // level time the prediction starts from
+}
+
+/// Predicts the path of the rocket, wrapping and bouncing at the level's bounds.
+/// The engine keeps its current throttle for the first `burn` seconds (or until the
+/// stage runs dry) and the rocket coasts after that. The path ends early if the
+/// rocket would hit a planet or an asteroid, or leave the play area, and the reason
+/// is returned alongside it.
+pub fn project_path(rocket: &Rocket, planet_defs: &[PlanetDef], asteroids: &[Asteroid], bounds: &Bounds, request: &PathRequest) -> (Vec<PathPoint>, Option<PathEnd>) {
+    let PathRequest { burn, duration, steps, start_time } = *request;
     let dt = duration / steps as f32;
+    let mut rocks = Forecast::new(asteroids);
     let mut sim = rocket.clone();
-    sim.engine_on = false;
+    sim.strafe_x = 0.0;
+    sim.strafe_y = 0.0;
     let mut path = Vec::with_capacity(steps);
     for i in 0..steps {
         let t = start_time + (i as f64 + 1.0) * dt as f64;
-        for def in planet_defs {
-            let planet = def.planet_at(t);
-            apply_gravity(&mut sim, &planet, dt);
+        let planets: Vec<_> = planet_defs.iter().map(|def| def.planet_at(t)).collect();
+        for planet in &planets {
+            apply_gravity(&mut sim, planet, dt);
         }
+        let density = air_density(&sim, &planets);
+        apply_drag(&mut sim, &planets, density, dt);
+        if i as f32 * dt >= burn {
+            sim.throttle = 0.0;
+        }
+        apply_engine(&mut sim, dt);
         move_rocket(&mut sim, dt);
-        path.push((sim.x, sim.y));
+        if !apply_bounds(&mut sim, bounds) {
+            return (path, Some(PathEnd::OutOfBounds));
+        }
+        path.push(PathPoint { x: sim.x, y: sim.y, speed_x: sim.speed_x, speed_y: sim.speed_y });
+        if let Some(planet) = planets.iter().find(|p| check_collision(&sim, p)) {
+            let end = if can_land(&sim, planet) { PathEnd::Landing } else { PathEnd::Crash };
+            return (path, Some(end));
+        }
+        rocks.step(&planets, t, dt);
+        if rocks.hits(sim.x, sim.y, t) {
+            return (path, Some(PathEnd::Crash));
+        }
+    }
+    (path, None)
+}
+
+/// A turning point of the path around the dominant body.
+pub struct Apsis {
+    pub index: usize,
+    pub altitude: f32,
+    pub periapsis: bool,
+}
+
+/// The point of the path closest to a planet's surface.
+pub struct Approach {
+    pub planet: usize,
+    pub index: usize,
+    pub altitude: f32,
+}
+
+/// The predicted trajectory plus everything worth pointing out along it.
+/// Point `i` is reached `(i + 1) * dt` seconds from now.
+pub struct Prediction {
+    pub points: Vec<PathPoint>,
+    pub dt: f32,
+    pub cutoff: Option<usize>, // last point with the engine still firing
+    pub end: Option<PathEnd>,
+    pub goal_entry: Option<(usize, bool)>, // first point inside the win circle, and whether it would count as a win
+    pub stranded: bool,                    // the path ends in a touchdown the engine can't lift off from again
+    pub dominant: Option<usize>,           // planet the apsides are measured against
+    pub apsides: Vec<Apsis>,
+    pub approaches: Vec<Approach>,
+}
+
+impl Prediction {
+    pub fn time_at(&self, index: usize) -> f32 {
+        (index as f32 + 1.0) * self.dt
+    }
+}
+
+/// Predicts the next `horizon` seconds of flight, burning at the current throttle
+/// for `burn` seconds first. Pass `0.0` for the coasting trajectory.
+pub fn predict(level: &LevelState, elapsed: f64, horizon: f32, burn: f32) -> Prediction {
+    let steps = ((horizon * 60.0).round() as usize).max(1);
+    let dt = horizon / steps as f32;
+    let planet_defs = &level.level.planets;
+    let (points, end) = project_path(&level.rocket, planet_defs, &level.asteroids, &level.level.bounds, &PathRequest { burn, duration: horizon, steps, start_time: elapsed });
+
+    let rocket = &level.rocket;
+    let cutoff = if rocket.throttle > 0.0 && burn > 0.0 {
+        let firing = burn.min(burn_time(rocket) / rocket.throttle);
+        let last = ((firing / dt).ceil() as usize).max(1) - 1;
+        Some(last.min(points.len().saturating_sub(1)))
+    } else {
+        None
+    };
+
+    let mut coasting = level.rocket.clone();
+    coasting.throttle = 0.0;
+    let goal_entry = points.iter().enumerate().find_map(|(i, p)| {
+        let condition = &level.level.win_condition;
+        if !in_win_circle(p.x, p.y, condition) {
+            return None;
+        }
+        coasting.x = p.x;
+        coasting.y = p.y;
+        coasting.speed_x = p.speed_x;
+        coasting.speed_y = p.speed_y;
+        Some((i, check_win(&coasting, condition)))
+    });
+
+    let altitudes: Vec<Vec<f32>> = planet_defs
+        .iter()
+        .map(|def| {
+            points
+                .iter()
+                .enumerate()
+                .map(|(i, p)| {
+                    let planet = def.planet_at(elapsed + (i as f64 + 1.0) * dt as f64);
+                    let dx = p.x - planet.x;
+                    let dy = p.y - planet.y;
+                    (dx * dx + dy * dy).sqrt() - planet.radius
+                })
+                .collect()
+        })
+        .collect();
+
+    let approaches = altitudes
+        .iter()
+        .enumerate()
+        .filter_map(|(planet, alts)| {
+            let (index, altitude) = alts.iter().copied().enumerate().min_by(|a, b| a.1.total_cmp(&b.1))?;
+            Some(Approach { planet, index, altitude })
+        })
+        .collect();
+
+    let current: Vec<_> = planet_defs.iter().map(|def| def.planet_at(elapsed)).collect();
+    let dominant = dominant_body(&level.rocket, &current);
+    let apsides = match dominant {
+        Some(d) => find_apsides(&altitudes[d]),
+        None => Vec::new(),
+    };
+
+    let stranded = end == Some(PathEnd::Landing)
+        && points.last().is_some_and(|last| {
+            let planets: Vec<_> = planet_defs.iter().map(|def| def.planet_at(elapsed + points.len() as f64 * dt as f64)).collect();
+            surface_under(last.x, last.y, &planets).is_some_and(|planet| !can_take_off(rocket, planet))
+        });
+
+    Prediction { points, dt, cutoff, end, goal_entry, stranded, dominant, apsides, approaches }
+}
+
+/// The planet pulling hardest on the rocket.
+pub fn dominant_body(rocket: &Rocket, planets: &[Planet]) -> Option<usize> {
+    planets
+        .iter()
+        .map(|p| {
+            let (ax, ay) = gravity_accel(rocket.x, rocket.y, p);
+            ax * ax + ay * ay
+        })
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+}
+
+/// The planet whose surface is closest to the rocket, with the altitude above it.
+pub fn nearest_surface(rocket: &Rocket, planets: &[Planet]) -> Option<(usize, f32)> {
+    planets
+        .iter()
+        .map(|p| {
+            let dx = rocket.x - p.x;
+            let dy = rocket.y - p.y;
+            (dx * dx + dy * dy).sqrt() - p.radius
+        })
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// Heading in degrees that the assist wants the rocket pointed at, clockwise
+/// from up like `Rocket::orientation`. `None` when there's nothing to point
+/// at, like prograde while at rest.
+pub fn assist_heading(assist: Assist, rocket: &Rocket, planets: &[Planet], condition: &WinCondition) -> Option<f32> {
+    let heading = |dx: f32, dy: f32| (dx != 0.0 || dy != 0.0).then(|| dx.atan2(-dy).to_degrees().rem_euclid(360.0));
+    if assist == Assist::Goal {
+        let (x, y) = match condition {
+            WinCondition::Circle { x, y, .. } | WinCondition::CircleAnySpeed { x, y, .. } => (*x, *y),
+        };
+        return heading(x - rocket.x, y - rocket.y);
+    }
+    let body = &planets[dominant_body(rocket, planets)?];
+    let (vx, vy) = airspeed(rocket, body);
+    let min_speed = 0.5;
+    match assist {
+        Assist::Prograde if vx * vx + vy * vy > min_speed * min_speed => heading(vx, vy),
+        Assist::Retrograde if vx * vx + vy * vy > min_speed * min_speed => heading(-vx, -vy),
+        Assist::RadialIn => heading(body.x - rocket.x, body.y - rocket.y),
+        Assist::RadialOut => heading(rocket.x - body.x, rocket.y - body.y),
+        _ => None,
+    }
+}
+
+/// Distance from the rocket to the edge of the goal circle (negative inside it)
+/// and how fast that distance is shrinking.
+pub fn goal_approach(rocket: &Rocket, condition: &WinCondition) -> (f32, f32) {
+    let (x, y, radius) = match condition {
+        WinCondition::Circle { x, y, radius, .. } | WinCondition::CircleAnySpeed { x, y, radius } => (*x, *y, *radius),
+    };
+    let dx = x - rocket.x;
+    let dy = y - rocket.y;
+    let dist = (dx * dx + dy * dy).sqrt();
+    let closing = if dist > 0.0 { (rocket.speed_x * dx + rocket.speed_y * dy) / dist } else { 0.0 };
+    (dist - radius, closing)
+}
+
+/// Local minima (periapsides) and maxima (apoapsides) of the altitude along a path.
+fn find_apsides(altitudes: &[f32]) -> Vec<Apsis> {
+    altitudes
+        .windows(3)
+        .enumerate()
+        .filter_map(|(i, w)| {
+            let index = i + 1;
+            if w[1] < w[0] && w[1] <= w[2] {
+                Some(Apsis { index, altitude: w[1], periapsis: true })
+            } else if w[1] > w[0] && w[1] >= w[2] {
+                Some(Apsis { index, altitude: w[1], periapsis: false })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn in_win_circle(x: f32, y: f32, condition: &WinCondition) -> bool {
+    match condition {
+        WinCondition::Circle { x: cx, y: cy, radius, .. }
+        | WinCondition::CircleAnySpeed { x: cx, y: cy, radius } => {
+            let dx = x - cx;
+            let dy = y - cy;
+            dx * dx + dy * dy < radius * radius
+        }
     }
-    path
 }
 
 pub fn check_collision(rocket: &Rocket, planet: &Planet) -> bool {
@@ -105,11 +714,17 @@ pub fn check_collision(rocket: &Rocket, planet: &Planet) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use crate::state::{Orbit, Rocket};
+    use crate::skins::Skin;
+    use crate::state::{Atmosphere, FuelCanister, Level, Orbit, Rocket, RotationMode};
     use super::*;
 
+    // 0.5 fuel mass lasts 20 seconds at full throttle
+    fn make_stage(fuel_mass: f32) -> Stage {
+        Stage { dry_mass: 0.5, fuel_mass, thrust: 10.0, isp: 40.0 }
+    }
+
     fn make_rocket(x: f32, y: f32, speed_x: f32, speed_y: f32) -> Rocket {
-        Rocket { x, y, speed_x, speed_y, orientation: 0.0, landed: false, engine_on: false, fuel: 20.0 }
+        Rocket { x, y, speed_x, speed_y, orientation: 0.0, landed: false, throttle: 0.0, stages: vec![make_stage(0.5)], heat: 0.0, angular_velocity: 0.0, rcs_fuel: 10.0, strafe_x: 0.0, strafe_y: 0.0 }
     }
 
     #[test]
@@ -153,7 +768,7 @@ mod tests {
     }
 
     fn make_planet(x: f32, y: f32) -> Planet {
-        Planet { x, y, radius: 30.0, image: 0 }
+        Planet { x, y, speed_x: 0.0, speed_y: 0.0, radius: 30.0, density: 1.0, image: 0, rotation: 0.0, atmosphere: None }
     }
 
     #[test]
@@ -208,8 +823,8 @@ mod tests {
     fn larger_planet_stronger_gravity() {
         let mut small = make_rocket(0.0, 0.0, 0.0, 0.0);
         let mut large = make_rocket(0.0, 0.0, 0.0, 0.0);
-        let small_planet = Planet { x: 100.0, y: 0.0, radius: 15.0, image: 0 };
-        let large_planet = Planet { x: 100.0, y: 0.0, radius: 30.0, image: 0 };
+        let small_planet = Planet { x: 100.0, y: 0.0, speed_x: 0.0, speed_y: 0.0, radius: 15.0, density: 1.0, image: 0, rotation: 0.0, atmosphere: None };
+        let large_planet = Planet { x: 100.0, y: 0.0, speed_x: 0.0, speed_y: 0.0, radius: 30.0, density: 1.0, image: 0, rotation: 0.0, atmosphere: None };
         apply_gravity(&mut small, &small_planet, 1.0);
         apply_gravity(&mut large, &large_planet, 1.0);
         assert!(large.speed_x > small.speed_x);
@@ -219,8 +834,8 @@ mod tests {
     fn gravity_proportional_to_radius_cubed() {
         let mut r30 = make_rocket(0.0, 0.0, 0.0, 0.0);
         let mut r60 = make_rocket(0.0, 0.0, 0.0, 0.0);
-        let p30 = Planet { x: 100.0, y: 0.0, radius: 30.0, image: 0 };
-        let p60 = Planet { x: 100.0, y: 0.0, radius: 60.0, image: 0 };
+        let p30 = Planet { x: 100.0, y: 0.0, speed_x: 0.0, speed_y: 0.0, radius: 30.0, density: 1.0, image: 0, rotation: 0.0, atmosphere: None };
+        let p60 = Planet { x: 100.0, y: 0.0, speed_x: 0.0, speed_y: 0.0, radius: 60.0, density: 1.0, image: 0, rotation: 0.0, atmosphere: None };
         apply_gravity(&mut r30, &p30, 1.0);
         apply_gravity(&mut r60, &p60, 1.0);
         // ratio should be (60/30)^3 = 8
@@ -232,8 +847,8 @@ mod tests {
     fn half_radius_one_eighth_gravity() {
         let mut full = make_rocket(0.0, 0.0, 0.0, 0.0);
         let mut half = make_rocket(0.0, 0.0, 0.0, 0.0);
-        let full_planet = Planet { x: 100.0, y: 0.0, radius: 30.0, image: 0 };
-        let half_planet = Planet { x: 100.0, y: 0.0, radius: 15.0, image: 0 };
+        let full_planet = Planet { x: 100.0, y: 0.0, speed_x: 0.0, speed_y: 0.0, radius: 30.0, density: 1.0, image: 0, rotation: 0.0, atmosphere: None };
+        let half_planet = Planet { x: 100.0, y: 0.0, speed_x: 0.0, speed_y: 0.0, radius: 15.0, density: 1.0, image: 0, rotation: 0.0, atmosphere: None };
         apply_gravity(&mut full, &full_planet, 1.0);
         apply_gravity(&mut half, &half_planet, 1.0);
         let ratio = half.speed_x / full.speed_x;
@@ -243,27 +858,27 @@ mod tests {
     #[test]
     fn thrust_acceleration_at_full_fuel() {
         let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
-        rocket.fuel = 20.0;
+        rocket.stages[0].fuel_mass = 0.5;
         apply_thrust(&mut rocket, 1.0);
-        // mass = 0.5 + 0.5 * (20/20) = 1.0, accel = 10/1 = 10
+        // mass = 0.5 dry + 0.5 fuel = 1.0, accel = 10/1 = 10
         assert!((rocket.speed_y - -10.0).abs() < f32::EPSILON);
     }
 
     #[test]
     fn thrust_acceleration_at_empty_fuel() {
         let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
-        rocket.fuel = 0.0;
+        rocket.stages[0].fuel_mass = 0.0;
         apply_thrust(&mut rocket, 1.0);
-        // mass = 0.5 + 0.5 * (0/20) = 0.5, accel = 10/0.5 = 20
+        // mass = 0.5 dry + 0 fuel = 0.5, accel = 10/0.5 = 20
         assert!((rocket.speed_y - -20.0).abs() < f32::EPSILON);
     }
 
     #[test]
     fn thrust_acceleration_at_half_fuel() {
         let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
-        rocket.fuel = 10.0;
+        rocket.stages[0].fuel_mass = 0.25;
         apply_thrust(&mut rocket, 1.0);
-        // mass = 0.5 + 0.5 * (10/20) = 0.75, accel = 10/0.75 = 13.333...
+        // mass = 0.5 dry + 0.25 fuel = 0.75, accel = 10/0.75 = 13.333...
         let expected = 10.0 / 0.75;
         assert!((rocket.speed_y - -expected).abs() < 0.001);
     }
@@ -271,9 +886,9 @@ mod tests {
     #[test]
     fn thrust_stronger_with_less_fuel() {
         let mut full = make_rocket(0.0, 0.0, 0.0, 0.0);
-        full.fuel = 20.0;
+        full.stages[0].fuel_mass = 0.5;
         let mut low = make_rocket(0.0, 0.0, 0.0, 0.0);
-        low.fuel = 5.0;
+        low.stages[0].fuel_mass = 0.125;
         apply_thrust(&mut full, 1.0);
         apply_thrust(&mut low, 1.0);
         assert!(low.speed_y.abs() > full.speed_y.abs());
@@ -282,27 +897,47 @@ mod tests {
     #[test]
     fn engine_off_when_fuel_runs_out() {
         let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
-        rocket.engine_on = true;
-        rocket.fuel = 0.5;
+        rocket.throttle = 1.0;
+        rocket.stages[0].fuel_mass = 0.0125;
         apply_engine(&mut rocket, 1.0);
-        assert_eq!(rocket.fuel, 0.0);
-        assert!(!rocket.engine_on);
+        assert_eq!(rocket.fuel(), 0.0);
+        assert_eq!(rocket.throttle, 0.0);
     }
 
     #[test]
     fn no_thrust_when_no_fuel() {
         let mut with_fuel = make_rocket(0.0, 0.0, 0.0, 0.0);
-        with_fuel.engine_on = true;
-        with_fuel.fuel = 20.0;
+        with_fuel.throttle = 1.0;
+        with_fuel.stages[0].fuel_mass = 0.5;
         let mut without_fuel = make_rocket(0.0, 0.0, 0.0, 0.0);
-        without_fuel.engine_on = true;
-        without_fuel.fuel = 0.0;
+        without_fuel.throttle = 1.0;
+        without_fuel.stages[0].fuel_mass = 0.0;
         apply_engine(&mut with_fuel, 1.0);
         apply_engine(&mut without_fuel, 1.0);
         // with fuel, thrust applies; without fuel, no thrust
         assert!(with_fuel.speed_y < without_fuel.speed_y);
     }
 
+    #[test]
+    fn partial_throttle_scales_thrust_and_fuel() {
+        let mut full = make_rocket(0.0, 0.0, 0.0, 0.0);
+        full.throttle = 1.0;
+        let mut half = make_rocket(0.0, 0.0, 0.0, 0.0);
+        half.throttle = 0.5;
+        apply_engine(&mut full, 0.01);
+        apply_engine(&mut half, 0.01);
+        assert!((half.speed_y * 2.0 - full.speed_y).abs() < 0.001);
+        assert!(((0.5 - half.fuel()) * 2.0 - (0.5 - full.fuel())).abs() < 0.0001);
+    }
+
+    #[test]
+    fn zero_throttle_burns_no_fuel() {
+        let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        apply_engine(&mut rocket, 1.0);
+        assert_eq!(rocket.fuel(), 0.5);
+        assert_eq!(rocket.speed_y, 0.0);
+    }
+
     #[test]
     fn collision_inside_planet() {
         let rocket = make_rocket(100.0, 100.0, 0.0, 0.0);
@@ -338,32 +973,784 @@ mod tests {
         assert!(check_collision(&rocket, &planet));
     }
 
+    #[test]
+    fn rotates_with_angular_velocity() {
+        let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        rocket.angular_velocity = 45.0;
+        rotate_rocket(&mut rocket, 2.0);
+        assert_eq!(rocket.orientation, 90.0);
+    }
+
+    #[test]
+    fn rotation_wraps_below_zero() {
+        let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        rocket.orientation = 10.0;
+        rocket.angular_velocity = -20.0;
+        rotate_rocket(&mut rocket, 1.0);
+        assert_eq!(rocket.orientation, 350.0);
+    }
+
+    #[test]
+    fn rcs_forward_follows_orientation() {
+        let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        rocket.orientation = 90.0;
+        rocket.strafe_y = 1.0;
+        apply_rcs(&mut rocket, 1.0);
+        assert!((rocket.speed_x - 3.0).abs() < 0.001);
+        assert!(rocket.speed_y.abs() < 0.001);
+        assert_eq!(rocket.rcs_fuel, 9.0);
+    }
+
+    #[test]
+    fn rcs_right_is_perpendicular_to_nose() {
+        let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        rocket.strafe_x = 1.0;
+        apply_rcs(&mut rocket, 1.0);
+        // pointing up, right is +x
+        assert!((rocket.speed_x - 3.0).abs() < 0.001);
+        assert!(rocket.speed_y.abs() < 0.001);
+    }
+
+    #[test]
+    fn rcs_uses_fuel_per_thruster() {
+        let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        rocket.strafe_x = -1.0;
+        rocket.strafe_y = -1.0;
+        apply_rcs(&mut rocket, 1.0);
+        assert_eq!(rocket.rcs_fuel, 8.0);
+        assert!(rocket.speed_x < 0.0);
+        assert!(rocket.speed_y > 0.0);
+    }
+
+    #[test]
+    fn no_rcs_translation_without_fuel() {
+        let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        rocket.rcs_fuel = 0.0;
+        rocket.strafe_y = 1.0;
+        apply_rcs(&mut rocket, 1.0);
+        assert_eq!(rocket.speed_y, 0.0);
+    }
+
     // --- engine_accel tests ---
 
     #[test]
     fn engine_accel_full_fuel() {
         let rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
-        // mass = 0.5 + 0.5 * (20/20) = 1.0, accel = 10/1 = 10
+        // mass = 0.5 dry + 0.5 fuel = 1.0, accel = 10/1 = 10
         assert!((engine_accel(&rocket) - 10.0).abs() < f32::EPSILON);
     }
 
     #[test]
     fn engine_accel_empty_fuel() {
         let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
-        rocket.fuel = 0.0;
-        // mass = 0.5 + 0.5 * 0 = 0.5, accel = 10/0.5 = 20
+        rocket.stages[0].fuel_mass = 0.0;
+        // mass = 0.5 dry + 0 fuel = 0.5, accel = 10/0.5 = 20
         assert!((engine_accel(&rocket) - 20.0).abs() < f32::EPSILON);
     }
 
     #[test]
     fn engine_accel_half_fuel() {
         let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
-        rocket.fuel = 10.0;
-        // mass = 0.5 + 0.5 * (10/20) = 0.75, accel = 10/0.75
+        rocket.stages[0].fuel_mass = 0.25;
+        // mass = 0.5 dry + 0.25 fuel = 0.75, accel = 10/0.75
         let expected = 10.0 / 0.75;
         assert!((engine_accel(&rocket) - expected).abs() < 0.001);
     }
 
+    #[test]
+    fn engine_accel_without_stages() {
+        let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        rocket.stages.clear();
+        assert_eq!(engine_accel(&rocket), 0.0);
+    }
+
+    // --- staging tests ---
+
+    fn make_two_stage_rocket() -> Rocket {
+        let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        rocket.stages = vec![
+            Stage { dry_mass: 1.0, fuel_mass: 2.0, thrust: 40.0, isp: 20.0 },
+            Stage { dry_mass: 0.5, fuel_mass: 0.5, thrust: 10.0, isp: 40.0 },
+        ];
+        rocket
+    }
+
+    fn make_level_state(rocket: Rocket) -> LevelState {
+        LevelState {
+            level: Level {
+                name: "Test",
+                planets: vec![],
+                initial_rocket: rocket.clone(),
+                win_condition: WinCondition::CircleAnySpeed { x: 0.0, y: 0.0, radius: 1.0 },
+                rotation: RotationMode::Instant,
+                canisters: vec![],
+                depots: vec![],
+                hazards: vec![],
+                bounds: Bounds::Open,
+                world: vec2(100.0, 50.0),
+                star: None,
+                assists: true,
+                seed: None,
+                par: None,
+            },
+            rocket,
+            debris: vec![],
+            collected: vec![],
+            asteroids: vec![],
+            time: 0.0,
+            started: 0.0,
+            trail: vec![],
+            past_trails: vec![],
+            grid: hazards::SpatialGrid::build(&[], hazards::CELL_SIZE),
+        }
+    }
+
+    fn make_game(rocket: Rocket) -> GameState {
+        GameState::from_state(make_level_state(rocket), Skin::all()[0].clone(), vec2(100.0, 50.0))
+    }
+
+    #[test]
+    fn update_advances_sim_time() {
+        let mut game = make_game(make_rocket(0.0, 0.0, 10.0, 0.0));
+        update(&mut game, 0.5);
+        update(&mut game, 0.25);
+        assert_eq!(game.level.time, 0.75);
+        assert_eq!(game.level.rocket.x, 7.5);
+    }
+
+    #[test]
+    fn trail_follows_rocket() {
+        let mut game = make_game(make_rocket(0.0, 0.0, 100.0, 0.0));
+        for _ in 0..10 {
+            update(&mut game, 0.01);
+        }
+        // one point per 4 px after the first
+        assert_eq!(game.level.trail.len(), 3);
+        assert!(game.level.trail.iter().all(|p| p.speed == 100.0 && !p.burning));
+        game.level.rocket.throttle = 1.0;
+        update(&mut game, 0.001);
+        assert!(game.level.trail.last().unwrap().burning);
+    }
+
+    #[test]
+    fn trail_forgets_old_points() {
+        let mut game = make_game(make_rocket(0.0, 0.0, 1.0, 0.0));
+        for _ in 0..40 {
+            update(&mut game, 1.0);
+        }
+        assert!(game.level.trail.iter().all(|p| game.level.time - p.time <= 30.0));
+        assert!(game.level.trail.first().unwrap().x > 5.0);
+    }
+
+    #[test]
+    fn failed_attempts_keep_their_trails() {
+        let mut level = make_level_state(make_rocket(0.0, 0.0, 0.0, 0.0));
+        for attempt in 0..5 {
+            level.trail.push(TrailPoint { x: attempt as f32, y: 0.0, speed: 0.0, burning: false, time: 0.0 });
+            level.reset_rocket();
+        }
+        assert!(level.trail.is_empty());
+        assert_eq!(level.past_trails.len(), 3);
+        assert_eq!(level.past_trails[0][0].x, 2.0);
+        assert_eq!(level.past_trails[2][0].x, 4.0);
+    }
+
+    #[test]
+    fn crash_leaves_wreck_in_place() {
+        let mut game = make_game(make_rocket(0.0, 0.0, 0.0, 0.0));
+        game.level.level.planets = vec![make_static_planet_def(0.0, 5.0, 20.0)];
+        game.level.rocket.speed_y = 200.0;
+        assert!(matches!(update(&mut game, 0.01), Some(PhysicsEvent::Collision)));
+        let crash = game.crash.clone().expect("should be crashed");
+        assert_eq!(crash.cause, CrashCause::Collision);
+        assert!(game.particles.alive().count() > 0);
+        // the rocket isn't simulated any more, but the world keeps going in slow motion
+        assert!(update(&mut game, 0.1).is_none());
+        assert_eq!(game.level.rocket.x, crash.x);
+        assert_eq!(game.level.rocket.y, crash.y);
+        assert!((game.level.time - 0.035).abs() < 1e-6);
+    }
+
+    #[test]
+    fn crash_restarts_on_its_own_and_is_remembered() {
+        let mut game = make_game(make_rocket(0.0, 0.0, 0.0, 0.0));
+        game.level.level.bounds = Bounds::Lose(screen_rect());
+        game.level.rocket.x = -1.0;
+        assert!(matches!(update(&mut game, 0.01), Some(PhysicsEvent::OutOfBounds)));
+        for _ in 0..60 {
+            update(&mut game, 0.1);
+        }
+        assert!(game.crash.is_none());
+        assert_eq!(game.level.rocket.x, game.level.level.initial_rocket.x);
+        assert!(game.last_crash.is_some_and(|p| p.x < 0.0));
+    }
+
+    #[test]
+    fn rocket_mass_sums_all_stages() {
+        let rocket = make_two_stage_rocket();
+        assert_eq!(rocket_mass(&rocket), 4.0);
+    }
+
+    #[test]
+    fn engine_accel_uses_active_stage_thrust() {
+        let rocket = make_two_stage_rocket();
+        assert_eq!(engine_accel(&rocket), 10.0);
+    }
+
+    #[test]
+    fn burn_time_from_mass_flow() {
+        let rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        assert!((burn_time(&rocket) - 20.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn burns_fuel_from_active_stage_only() {
+        let mut rocket = make_two_stage_rocket();
+        rocket.throttle = 1.0;
+        apply_engine(&mut rocket, 1.0);
+        // 40 / (20 * 10) = 0.2 per second
+        assert!((rocket.stages[0].fuel_mass - 1.8).abs() < 0.0001);
+        assert_eq!(rocket.stages[1].fuel_mass, 0.5);
+    }
+
+    #[test]
+    fn delta_v_per_stage() {
+        let rocket = make_two_stage_rocket();
+        let dv = stage_delta_v(&rocket);
+        assert_eq!(dv.len(), 2);
+        // first stage pushes everything: 200 * ln(4 / 2)
+        assert!((dv[0] - 200.0 * 2.0_f32.ln()).abs() < 0.01);
+        // second stage pushes only itself: 400 * ln(1 / 0.5)
+        assert!((dv[1] - 400.0 * 2.0_f32.ln()).abs() < 0.01);
+    }
+
+    #[test]
+    fn empty_stage_has_no_delta_v() {
+        let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        rocket.stages[0].fuel_mass = 0.0;
+        assert_eq!(stage_delta_v(&rocket), vec![0.0]);
+    }
+
+    #[test]
+    fn jettison_drops_active_stage_as_debris() {
+        let mut level = make_level_state(make_two_stage_rocket());
+        level.rocket.speed_x = 50.0;
+        level.rocket.throttle = 1.0;
+        assert!(jettison_stage(&mut level));
+        assert_eq!(level.rocket.stages.len(), 1);
+        assert_eq!(level.rocket.stages[0].thrust, 10.0);
+        assert_eq!(level.rocket.throttle, 0.0);
+        assert_eq!(level.debris.len(), 1);
+        // pointing up, the spent stage separates downwards
+        assert_eq!(level.debris[0].speed_x, 50.0);
+        assert!(level.debris[0].speed_y > 0.0);
+    }
+
+    #[test]
+    fn cannot_jettison_last_stage() {
+        let mut level = make_level_state(make_rocket(0.0, 0.0, 0.0, 0.0));
+        assert!(!jettison_stage(&mut level));
+        assert_eq!(level.rocket.stages.len(), 1);
+        assert!(level.debris.is_empty());
+    }
+
+    #[test]
+    fn debris_falls_under_gravity() {
+        let mut debris = vec![Debris { x: 0.0, y: 0.0, speed_x: 0.0, speed_y: 0.0, orientation: 0.0 }];
+        update_debris(&mut debris, &[make_planet(100.0, 0.0)], 0.1);
+        assert!(debris[0].speed_x > 0.0);
+        assert!(debris[0].x > 0.0);
+    }
+
+    #[test]
+    fn debris_removed_on_impact() {
+        let mut debris = vec![Debris { x: 69.0, y: 0.0, speed_x: 100.0, speed_y: 0.0, orientation: 0.0 }];
+        update_debris(&mut debris, &[make_planet(100.0, 0.0)], 0.01);
+        assert!(debris.is_empty());
+    }
+
+    #[test]
+    fn density_scales_gravity() {
+        let mut normal = make_rocket(0.0, 0.0, 0.0, 0.0);
+        let mut light = make_rocket(0.0, 0.0, 0.0, 0.0);
+        let mut light_planet = make_planet(100.0, 0.0);
+        light_planet.density = 0.5;
+        apply_gravity(&mut normal, &make_planet(100.0, 0.0), 1.0);
+        apply_gravity(&mut light, &light_planet, 1.0);
+        assert!((light.speed_x * 2.0 - normal.speed_x).abs() < 0.001);
+    }
+
+    // --- landing and refuelling tests ---
+
+    #[test]
+    fn gentle_touchdown_lands() {
+        let rocket = make_rocket(0.0, 0.0, 0.0, 10.0);
+        assert!(can_land(&rocket, &make_planet(0.0, 20.0)));
+    }
+
+    #[test]
+    fn fast_touchdown_does_not_land() {
+        let rocket = make_rocket(0.0, 0.0, 0.0, 100.0);
+        assert!(!can_land(&rocket, &make_planet(0.0, 20.0)));
+    }
+
+    #[test]
+    fn landing_speed_relative_to_planet() {
+        let rocket = make_rocket(0.0, 0.0, 100.0, 0.0);
+        let mut planet = make_planet(0.0, 20.0);
+        planet.speed_x = 95.0;
+        assert!(can_land(&rocket, &planet));
+    }
+
+    #[test]
+    fn landing_puts_rocket_on_surface() {
+        let mut rocket = make_rocket(0.0, 5.0, 3.0, 10.0);
+        let mut planet = make_planet(0.0, 20.0);
+        planet.speed_x = 7.0;
+        land(&mut rocket, &planet);
+        assert!(rocket.landed);
+        assert!(rocket.x.abs() < 0.001);
+        assert!((rocket.y - -10.0).abs() < 0.001);
+        assert_eq!(rocket.speed_x, 7.0);
+        assert_eq!(rocket.speed_y, 0.0);
+        assert!(!check_collision(&rocket, &planet));
+    }
+
+    #[test]
+    fn engine_must_beat_surface_gravity_to_take_off() {
+        let rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        assert!(!can_take_off(&rocket, &make_planet(0.0, 40.0)));
+        let moon = Planet { density: 0.0001, ..make_planet(0.0, 40.0) };
+        assert!(can_take_off(&rocket, &moon));
+    }
+
+    #[test]
+    fn gentle_touchdown_on_heavy_planet_lands_stranded() {
+        let mut game = make_game(make_rocket(0.0, 0.0, 0.0, 10.0));
+        game.level.level.win_condition = WinCondition::CircleAnySpeed { x: 500.0, y: 0.0, radius: 1.0 };
+        game.level.level.planets = vec![PlanetDef { density: 0.1, ..make_static_planet_def(0.0, 20.5, 20.0) }];
+        for _ in 0..20 {
+            assert!(update(&mut game, 0.01).is_none());
+        }
+        assert!(game.level.rocket.landed);
+        assert!(stranded(&game.level));
+        // full throttle doesn't get it off the ground
+        game.level.rocket.throttle = 1.0;
+        for _ in 0..100 {
+            assert!(update(&mut game, 0.01).is_none());
+        }
+        assert!(game.level.rocket.landed);
+    }
+
+    #[test]
+    fn prediction_warns_of_stranding_touchdown() {
+        let mut level = make_level_state(make_rocket(0.0, -35.0, 0.0, 5.0));
+        level.level.win_condition = WinCondition::CircleAnySpeed { x: 500.0, y: 0.0, radius: 1.0 };
+        level.level.planets = vec![PlanetDef { density: 0.01, ..make_static_planet_def(0.0, 0.0, 30.0) }];
+        let prediction = predict(&level, 0.0, 5.0, 0.0);
+        assert_eq!(prediction.end, Some(PathEnd::Landing));
+        assert!(prediction.stranded);
+        level.level.planets[0].density = 0.0001;
+        let prediction = predict(&level, 0.0, 5.0, 0.0);
+        assert_eq!(prediction.end, Some(PathEnd::Landing));
+        assert!(!prediction.stranded);
+    }
+
+    #[test]
+    fn lands_on_light_moon_and_lifts_off() {
+        let mut game = make_game(make_rocket(0.0, 0.0, 0.0, 10.0));
+        let moon = PlanetDef { density: 0.0001, ..make_static_planet_def(0.0, 20.5, 20.0) };
+        game.level.level.win_condition = WinCondition::CircleAnySpeed { x: 500.0, y: 0.0, radius: 1.0 };
+        game.level.level.planets = vec![moon];
+        for _ in 0..20 {
+            assert!(update(&mut game, 0.01).is_none());
+        }
+        assert!(game.level.rocket.landed);
+        // nose up, away from the moon below
+        game.level.rocket.throttle = 1.0;
+        for _ in 0..100 {
+            assert!(update(&mut game, 0.01).is_none());
+        }
+        assert!(!game.level.rocket.landed);
+        assert!(game.level.rocket.y < -1.0);
+    }
+
+    #[test]
+    fn touchdown_kicks_up_dust() {
+        let mut game = make_game(make_rocket(0.0, 0.0, 0.0, 10.0));
+        let moon = PlanetDef { density: 0.0001, ..make_static_planet_def(0.0, 20.5, 20.0) };
+        game.level.level.win_condition = WinCondition::CircleAnySpeed { x: 500.0, y: 0.0, radius: 1.0 };
+        game.level.level.planets = vec![moon];
+        for _ in 0..20 {
+            assert!(update(&mut game, 0.01).is_none());
+        }
+        assert!(game.level.rocket.landed);
+        assert!(game.particles.alive().count() > 0);
+    }
+
+    #[test]
+    fn step_flies_like_update() {
+        let mut game = make_game(make_rocket(0.0, 0.0, 10.0, 0.0));
+        game.level.level.win_condition = WinCondition::CircleAnySpeed { x: 500.0, y: 500.0, radius: 1.0 };
+        game.level.level.planets = vec![make_static_planet_def(0.0, 200.0, 20.0)];
+        game.level.rocket.throttle = 1.0;
+        let mut level = game.level.clone();
+        for _ in 0..100 {
+            assert!(update(&mut game, 0.01).is_none());
+            assert!(step(&mut level, 0.01).is_none());
+        }
+        assert_eq!((level.rocket.x, level.rocket.y), (game.level.rocket.x, game.level.rocket.y));
+        assert_eq!(level.rocket.fuel(), game.level.rocket.fuel());
+    }
+
+    #[test]
+    fn depot_moons_can_be_left() {
+        for level in Level::all() {
+            for depot in &level.depots {
+                let planet = level.planets[depot.planet].planet_at(0.0);
+                assert!(can_take_off(&level.initial_rocket, &planet), "{}", level.name);
+            }
+        }
+    }
+
+    #[test]
+    fn landed_rocket_hit_hard_crashes() {
+        let mut game = make_game(make_rocket(0.0, 0.0, 0.0, 0.0));
+        let moon = PlanetDef { density: 0.0001, ..make_static_planet_def(0.0, 20.0, 20.0) };
+        game.level.level.planets = vec![moon];
+        game.level.rocket.landed = true;
+        game.level.rocket.speed_y = 200.0;
+        assert!(matches!(update(&mut game, 0.01), Some(PhysicsEvent::Collision)));
+    }
+
+    fn make_canister(x: f32, y: f32) -> FuelCanister {
+        FuelCanister { center_x: x, center_y: y, orbit: None, fuel_mass: 0.1 }
+    }
+
+    #[test]
+    fn collects_canister_once() {
+        let mut level = make_level_state(make_rocket(0.0, 0.0, 0.0, 0.0));
+        level.level.canisters = vec![make_canister(5.0, 0.0)];
+        level.collected = vec![false];
+        collect_canisters(&mut level, 0.0);
+        assert!(level.collected[0]);
+        assert!((level.rocket.fuel() - 0.6).abs() < 0.0001);
+        collect_canisters(&mut level, 0.0);
+        assert!((level.rocket.fuel() - 0.6).abs() < 0.0001);
+    }
+
+    #[test]
+    fn ignores_distant_canister() {
+        let mut level = make_level_state(make_rocket(0.0, 0.0, 0.0, 0.0));
+        level.level.canisters = vec![make_canister(100.0, 0.0)];
+        level.collected = vec![false];
+        collect_canisters(&mut level, 0.0);
+        assert!(!level.collected[0]);
+        assert_eq!(level.rocket.fuel(), 0.5);
+    }
+
+    #[test]
+    fn orbiting_canister_moves() {
+        let canister = FuelCanister {
+            center_x: 0.0,
+            center_y: 0.0,
+            orbit: Some(Orbit { radius: 100.0, speed: std::f32::consts::PI, initial_angle: 0.0 }),
+            fuel_mass: 0.1,
+        };
+        let (x, y) = canister.position_at(1.0);
+        assert!((x - -100.0).abs() < 0.01);
+        assert!(y.abs() < 0.01);
+    }
+
+    #[test]
+    fn reset_restores_canisters() {
+        let mut level = make_level_state(make_rocket(0.0, 0.0, 0.0, 0.0));
+        level.level.canisters = vec![make_canister(5.0, 0.0)];
+        level.collected = vec![true];
+        level.reset_rocket();
+        assert!(!level.collected[0]);
+    }
+
+    fn make_depot_level(fuel_mass: f32) -> (LevelState, Vec<Planet>) {
+        let mut level = make_level_state(make_rocket(0.0, -30.0, 0.0, 0.0));
+        level.rocket.stages[0].fuel_mass = fuel_mass;
+        level.rocket.landed = true;
+        level.level.depots = vec![FuelDepot { planet: 0, angle: -std::f32::consts::FRAC_PI_2, rate: 0.1 }];
+        (level, vec![make_planet(0.0, 0.0)])
+    }
+
+    #[test]
+    fn depot_refuels_landed_rocket() {
+        let (mut level, planets) = make_depot_level(0.1);
+        refuel_at_depots(&mut level, &planets, 1.0);
+        assert!((level.rocket.fuel() - 0.2).abs() < 0.0001);
+    }
+
+    #[test]
+    fn depot_fills_up_to_capacity() {
+        let (mut level, planets) = make_depot_level(0.45);
+        refuel_at_depots(&mut level, &planets, 1.0);
+        assert_eq!(level.rocket.fuel(), 0.5);
+    }
+
+    #[test]
+    fn depot_needs_rocket_landed() {
+        let (mut level, planets) = make_depot_level(0.1);
+        level.rocket.landed = false;
+        refuel_at_depots(&mut level, &planets, 1.0);
+        assert_eq!(level.rocket.fuel(), 0.1);
+    }
+
+    #[test]
+    fn depot_needs_rocket_on_pad() {
+        let (mut level, planets) = make_depot_level(0.1);
+        level.rocket.x = 30.0;
+        level.rocket.y = 0.0;
+        refuel_at_depots(&mut level, &planets, 1.0);
+        assert_eq!(level.rocket.fuel(), 0.1);
+    }
+
+    #[test]
+    fn project_path_stops_at_asteroid() {
+        let rocket = make_rocket(0.0, 0.0, 100.0, 0.0);
+        let asteroids = hazards::spawn(&[hazards::HazardDef::Drift { x: 50.0, y: 0.0, speed_x: 0.0, speed_y: 0.0, radius: 5.0 }]);
+        let (path, _) = project_path(&rocket, &[], &asteroids, &Bounds::Open, &PathRequest { burn: 0.0, duration: 1.0, steps: 100, start_time: 0.0 });
+        assert!(path.len() < 100);
+        assert!(path.last().unwrap().x < 50.0);
+    }
+
+    #[test]
+    fn project_path_sees_moving_asteroid() {
+        let rocket = make_rocket(0.0, 0.0, 100.0, 0.0);
+        // starts out of the way, but drifts into the rocket's path
+        let asteroids = hazards::spawn(&[hazards::HazardDef::Drift { x: 50.0, y: -50.0, speed_x: 0.0, speed_y: 100.0, radius: 5.0 }]);
+        let (path, _) = project_path(&rocket, &[], &asteroids, &Bounds::Open, &PathRequest { burn: 0.0, duration: 1.0, steps: 100, start_time: 0.0 });
+        assert!(path.len() < 100);
+    }
+
+    // --- bounds tests ---
+
+    fn screen_rect() -> Rect {
+        Rect::new(0.0, 0.0, 100.0, 50.0)
+    }
+
+    #[test]
+    fn open_bounds_never_lose() {
+        let mut rocket = make_rocket(-1000.0, 5000.0, 10.0, 0.0);
+        assert!(apply_bounds(&mut rocket, &Bounds::Open));
+        assert_eq!(rocket.x, -1000.0);
+    }
+
+    #[test]
+    fn wrap_bounds_wrap_around() {
+        let mut rocket = make_rocket(105.0, -10.0, 10.0, -3.0);
+        assert!(apply_bounds(&mut rocket, &Bounds::Wrap(screen_rect())));
+        assert!((rocket.x - 5.0).abs() < 0.001);
+        assert!((rocket.y - 40.0).abs() < 0.001);
+        assert_eq!(rocket.speed_x, 10.0);
+        assert_eq!(rocket.speed_y, -3.0);
+    }
+
+    #[test]
+    fn wall_bounds_bounce() {
+        let mut rocket = make_rocket(105.0, 20.0, 10.0, 4.0);
+        assert!(apply_bounds(&mut rocket, &Bounds::Wall { rect: screen_rect(), restitution: 0.5 }));
+        assert_eq!(rocket.x, 100.0);
+        assert_eq!(rocket.speed_x, -5.0);
+        assert_eq!(rocket.speed_y, 4.0);
+    }
+
+    #[test]
+    fn wall_bounds_leave_rocket_inside_alone() {
+        let mut rocket = make_rocket(50.0, 20.0, 10.0, 4.0);
+        assert!(apply_bounds(&mut rocket, &Bounds::Wall { rect: screen_rect(), restitution: 0.5 }));
+        assert_eq!(rocket.speed_x, 10.0);
+    }
+
+    #[test]
+    fn lose_bounds_fail_outside() {
+        let mut inside = make_rocket(50.0, 20.0, 0.0, 0.0);
+        let mut outside = make_rocket(50.0, 60.0, 0.0, 0.0);
+        assert!(apply_bounds(&mut inside, &Bounds::Lose(screen_rect())));
+        assert!(!apply_bounds(&mut outside, &Bounds::Lose(screen_rect())));
+    }
+
+    #[test]
+    fn project_path_wraps() {
+        let rocket = make_rocket(90.0, 20.0, 100.0, 0.0);
+        let (path, _) = project_path(&rocket, &[], &[], &Bounds::Wrap(screen_rect()), &PathRequest { burn: 0.0, duration: 0.5, steps: 10, start_time: 0.0 });
+        assert_eq!(path.len(), 10);
+        assert!(path.iter().all(|p| (0.0..100.0).contains(&p.x)));
+        assert!(path.last().unwrap().x < 90.0);
+    }
+
+    #[test]
+    fn project_path_stops_when_leaving_play_area() {
+        let rocket = make_rocket(90.0, 20.0, 100.0, 0.0);
+        let (path, _) = project_path(&rocket, &[], &[], &Bounds::Lose(screen_rect()), &PathRequest { burn: 0.0, duration: 0.5, steps: 10, start_time: 0.0 });
+        assert_eq!(path.len(), 1);
+    }
+
+    // --- prediction tests ---
+
+    #[test]
+    fn project_path_stops_at_planet() {
+        let rocket = make_rocket(0.0, 0.0, 100.0, 0.0);
+        let planets = vec![make_static_planet_def(100.0, 0.0, 30.0)];
+        let (points, end) = project_path(&rocket, &planets, &[], &Bounds::Open, &PathRequest { burn: 0.0, duration: 2.0, steps: 100, start_time: 0.0 });
+        assert!(points.len() < 100);
+        assert_eq!(end, Some(PathEnd::Crash));
+        let last = points.last().unwrap();
+        assert!(((last.x - 100.0).powi(2) + last.y.powi(2)).sqrt() < 30.0);
+    }
+
+    #[test]
+    fn project_path_predicts_landing() {
+        let rocket = make_rocket(0.0, -35.0, 0.0, 0.0);
+        let mut planets = vec![make_static_planet_def(0.0, 0.0, 30.0)];
+        planets[0].density = 0.01;
+        let (_, end) = project_path(&rocket, &planets, &[], &Bounds::Open, &PathRequest { burn: 0.0, duration: 5.0, steps: 300, start_time: 0.0 });
+        assert_eq!(end, Some(PathEnd::Landing));
+    }
+
+    #[test]
+    fn project_path_reports_leaving_play_area() {
+        let rocket = make_rocket(90.0, 20.0, 100.0, 0.0);
+        let (_, end) = project_path(&rocket, &[], &[], &Bounds::Lose(screen_rect()), &PathRequest { burn: 0.0, duration: 0.5, steps: 10, start_time: 0.0 });
+        assert_eq!(end, Some(PathEnd::OutOfBounds));
+    }
+
+    #[test]
+    fn coasting_path_has_no_end() {
+        let rocket = make_rocket(0.0, 0.0, 100.0, 0.0);
+        let (points, end) = project_path(&rocket, &[], &[], &Bounds::Open, &PathRequest { burn: 0.0, duration: 1.0, steps: 10, start_time: 0.0 });
+        assert_eq!(points.len(), 10);
+        assert_eq!(end, None);
+    }
+
+    #[test]
+    fn predict_finds_goal_entry() {
+        let mut level = make_level_state(make_rocket(0.0, 0.0, 100.0, 0.0));
+        level.level.win_condition = WinCondition::CircleAnySpeed { x: 200.0, y: 0.0, radius: 50.0 };
+        let prediction = predict(&level, 0.0, 3.0, 0.0);
+        let (index, wins) = prediction.goal_entry.unwrap();
+        assert!(wins);
+        // enters at x = 150 after 1.5 seconds
+        assert!((prediction.time_at(index) - 1.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn predict_goal_entry_too_fast() {
+        let mut level = make_level_state(make_rocket(0.0, 0.0, 100.0, 0.0));
+        level.level.win_condition = WinCondition::Circle { x: 200.0, y: 0.0, radius: 50.0, max_speed: 10.0 };
+        let prediction = predict(&level, 0.0, 3.0, 0.0);
+        assert_eq!(prediction.goal_entry.map(|(_, wins)| wins), Some(false));
+    }
+
+    #[test]
+    fn predict_closest_approach_per_planet() {
+        let mut level = make_level_state(make_rocket(0.0, 0.0, 100.0, 0.0));
+        level.level.planets = vec![make_static_planet_def(100.0, 2000.0, 10.0), make_static_planet_def(300.0, -3000.0, 10.0)];
+        let prediction = predict(&level, 0.0, 4.0, 0.0);
+        assert_eq!(prediction.approaches.len(), 2);
+        let first = &prediction.approaches[0];
+        assert_eq!(first.planet, 0);
+        // passes right over the planet at x = 100, one second in
+        assert!((prediction.time_at(first.index) - 1.0).abs() < 0.1);
+        assert!((first.altitude - 1990.0).abs() < 5.0);
+    }
+
+    #[test]
+    fn predict_apsides_of_elliptical_orbit() {
+        // slower than circular orbit speed at this distance, so the rocket falls inwards
+        let rocket = make_rocket(0.0, -300.0, 80.0, 0.0);
+        let mut level = make_level_state(rocket);
+        level.level.planets = vec![make_static_planet_def(0.0, 0.0, 30.0)];
+        let prediction = predict(&level, 0.0, 20.0, 0.0);
+        assert_eq!(prediction.dominant, Some(0));
+        let periapsis = prediction.apsides.iter().find(|a| a.periapsis).unwrap();
+        let apoapsis = prediction.apsides.iter().find(|a| !a.periapsis).unwrap();
+        assert!(periapsis.altitude < apoapsis.altitude);
+        assert!((apoapsis.altitude - 270.0).abs() < 5.0);
+    }
+
+    #[test]
+    fn find_apsides_in_altitudes() {
+        let apsides = find_apsides(&[5.0, 3.0, 1.0, 2.0, 4.0, 3.0]);
+        assert_eq!(apsides.len(), 2);
+        assert!(apsides[0].periapsis);
+        assert_eq!(apsides[0].index, 2);
+        assert!(!apsides[1].periapsis);
+        assert_eq!(apsides[1].index, 4);
+    }
+
+    #[test]
+    fn gravity_field_matches_apply_gravity() {
+        let planets = vec![make_planet(100.0, 0.0), make_planet(0.0, -60.0)];
+        let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        for planet in &planets {
+            apply_gravity(&mut rocket, planet, 1.0);
+        }
+        let (ax, ay) = gravity_field(0.0, 0.0, &planets);
+        assert!((ax - rocket.speed_x).abs() < 0.001);
+        assert!((ay - rocket.speed_y).abs() < 0.001);
+        assert!(ax > 0.0 && ay < 0.0);
+    }
+
+    #[test]
+    fn dominant_body_is_strongest_pull() {
+        let rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        let near_small = Planet { radius: 10.0, ..make_planet(20.0, 0.0) };
+        let far_large = Planet { radius: 60.0, ..make_planet(-500.0, 0.0) };
+        assert_eq!(dominant_body(&rocket, &[near_small.clone(), far_large.clone()]), Some(0));
+        let far_small = Planet { radius: 10.0, ..make_planet(900.0, 0.0) };
+        assert_eq!(dominant_body(&rocket, &[far_small, far_large]), Some(1));
+    }
+
+    #[test]
+    fn nearest_surface_accounts_for_radius() {
+        let rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        let small = Planet { radius: 10.0, ..make_planet(100.0, 0.0) };
+        let large = Planet { radius: 80.0, ..make_planet(-120.0, 0.0) };
+        assert_eq!(nearest_surface(&rocket, &[small, large]), Some((1, 40.0)));
+        assert_eq!(nearest_surface(&rocket, &[]), None);
+    }
+
+    #[test]
+    fn goal_approach_measures_closing_speed() {
+        let condition = WinCondition::CircleAnySpeed { x: 100.0, y: 0.0, radius: 20.0 };
+        let (distance, closing) = goal_approach(&make_rocket(0.0, 0.0, 30.0, 40.0), &condition);
+        assert_eq!(distance, 80.0);
+        assert_eq!(closing, 30.0);
+        let (_, receding) = goal_approach(&make_rocket(0.0, 0.0, -10.0, 0.0), &condition);
+        assert_eq!(receding, -10.0);
+    }
+
+    #[test]
+    fn assist_headings_follow_velocity_and_body() {
+        let rocket = make_rocket(0.0, 0.0, 10.0, 0.0);
+        let planets = [make_planet(0.0, 100.0)];
+        let goal = WinCondition::CircleAnySpeed { x: -50.0, y: 0.0, radius: 10.0 };
+        let heading = |assist| assist_heading(assist, &rocket, &planets, &goal).unwrap();
+        assert_eq!(heading(Assist::Prograde), 90.0);
+        assert_eq!(heading(Assist::Retrograde), 270.0);
+        assert_eq!(heading(Assist::RadialIn), 180.0);
+        assert_eq!(heading(Assist::RadialOut), 0.0);
+        assert_eq!(heading(Assist::Goal), 270.0);
+    }
+
+    #[test]
+    fn prograde_is_relative_to_dominant_body() {
+        let rocket = make_rocket(0.0, 0.0, 10.0, 0.0);
+        let moving = [Planet { speed_x: 10.0, speed_y: -10.0, ..make_planet(0.0, 100.0) }];
+        let goal = WinCondition::CircleAnySpeed { x: 0.0, y: 0.0, radius: 10.0 };
+        // moving along with the planet's x speed, the rocket drifts down relative to it
+        assert_eq!(assist_heading(Assist::Prograde, &rocket, &moving, &goal), Some(180.0));
+        let at_rest = Rocket { speed_x: 10.0, speed_y: -10.0, ..rocket };
+        assert_eq!(assist_heading(Assist::Prograde, &at_rest, &moving, &goal), None);
+        assert_eq!(assist_heading(Assist::RadialIn, &at_rest, &[], &goal), None);
+    }
+
     // --- check_win tests ---
 
     #[test]
@@ -383,7 +1770,15 @@ mod tests {
     #[test]
     fn no_win_circle_engine_on() {
         let mut rocket = make_rocket(100.0, 100.0, 1.0, 0.0);
-        rocket.engine_on = true;
+        rocket.throttle = 1.0;
+        let condition = WinCondition::Circle { x: 100.0, y: 100.0, radius: 50.0, max_speed: 2.0 };
+        assert!(!check_win(&rocket, &condition));
+    }
+
+    #[test]
+    fn no_win_circle_partial_throttle() {
+        let mut rocket = make_rocket(100.0, 100.0, 1.0, 0.0);
+        rocket.throttle = 0.1;
         let condition = WinCondition::Circle { x: 100.0, y: 100.0, radius: 50.0, max_speed: 2.0 };
         assert!(!check_win(&rocket, &condition));
     }
@@ -405,7 +1800,7 @@ mod tests {
     #[test]
     fn no_win_circle_any_speed_engine_on() {
         let mut rocket = make_rocket(100.0, 100.0, 0.0, 0.0);
-        rocket.engine_on = true;
+        rocket.throttle = 1.0;
         let condition = WinCondition::CircleAnySpeed { x: 100.0, y: 100.0, radius: 50.0 };
         assert!(!check_win(&rocket, &condition));
     }
@@ -420,25 +1815,25 @@ mod tests {
     // --- project_path tests ---
 
     fn make_static_planet_def(x: f32, y: f32, radius: f32) -> PlanetDef {
-        PlanetDef { center_x: x, center_y: y, radius, image: 0, orbit: None }
+        PlanetDef { center_x: x, center_y: y, radius, density: 1.0, image: 0, spin: 0.0, orbit: None, atmosphere: None }
     }
 
     #[test]
     fn project_path_no_planets_straight_line() {
         let rocket = make_rocket(0.0, 0.0, 100.0, 0.0);
-        let path = project_path(&rocket, &[], 1.0, 10, 0.0);
+        let (path, _) = project_path(&rocket, &[], &[], &Bounds::Open, &PathRequest { burn: 0.0, duration: 1.0, steps: 10, start_time: 0.0 });
         assert_eq!(path.len(), 10);
         // should move right in a straight line
         for i in 1..path.len() {
-            assert!(path[i].0 > path[i - 1].0);
-            assert!((path[i].1).abs() < f32::EPSILON);
+            assert!(path[i].x > path[i - 1].x);
+            assert!((path[i].y).abs() < f32::EPSILON);
         }
     }
 
     #[test]
     fn project_path_returns_correct_count() {
         let rocket = make_rocket(0.0, 0.0, 10.0, 0.0);
-        let path = project_path(&rocket, &[], 2.0, 50, 0.0);
+        let (path, _) = project_path(&rocket, &[], &[], &Bounds::Open, &PathRequest { burn: 0.0, duration: 2.0, steps: 50, start_time: 0.0 });
         assert_eq!(path.len(), 50);
     }
 
@@ -446,10 +1841,10 @@ mod tests {
     fn project_path_static_planet_curves_trajectory() {
         let rocket = make_rocket(0.0, 0.0, 100.0, 0.0);
         let planets = vec![make_static_planet_def(0.0, 200.0, 10.0)];
-        let path = project_path(&rocket, &planets, 2.0, 100, 0.0);
+        let (path, _) = project_path(&rocket, &planets, &[], &Bounds::Open, &PathRequest { burn: 0.0, duration: 2.0, steps: 100, start_time: 0.0 });
         // planet is below, so rocket should curve downward (positive y)
         let last = path.last().unwrap();
-        assert!(last.1 > 0.0);
+        assert!(last.y > 0.0);
     }
 
     #[test]
@@ -460,27 +1855,205 @@ mod tests {
             center_x: 0.0,
             center_y: 200.0,
             radius: 10.0,
+            density: 1.0,
             image: 0,
+            spin: 0.0,
             orbit: Some(Orbit { radius: 100.0, speed: 2.0, initial_angle: 0.0 }),
+            atmosphere: None,
         }];
-        let static_path = project_path(&rocket, &static_planets, 2.0, 100, 0.0);
-        let orbiting_path = project_path(&rocket, &orbiting_planets, 2.0, 100, 0.0);
+        let (static_path, _) = project_path(&rocket, &static_planets, &[], &Bounds::Open, &PathRequest { burn: 0.0, duration: 2.0, steps: 100, start_time: 0.0 });
+        let (orbiting_path, _) = project_path(&rocket, &orbiting_planets, &[], &Bounds::Open, &PathRequest { burn: 0.0, duration: 2.0, steps: 100, start_time: 0.0 });
         // paths should diverge since the orbiting planet moves
         let last_static = static_path.last().unwrap();
         let last_orbit = orbiting_path.last().unwrap();
-        let dx = last_static.0 - last_orbit.0;
-        let dy = last_static.1 - last_orbit.1;
+        let dx = last_static.x - last_orbit.x;
+        let dy = last_static.y - last_orbit.y;
         assert!(dx * dx + dy * dy > 1.0);
     }
 
     #[test]
     fn project_path_disables_engine() {
         let mut rocket = make_rocket(0.0, 0.0, 100.0, 0.0);
-        rocket.engine_on = true;
-        let path = project_path(&rocket, &[], 1.0, 10, 0.0);
+        rocket.throttle = 1.0;
+        let (path, _) = project_path(&rocket, &[], &[], &Bounds::Open, &PathRequest { burn: 0.0, duration: 1.0, steps: 10, start_time: 0.0 });
         // with engine disabled, should move in straight line (no thrust)
-        for i in 1..path.len() {
-            assert!((path[i].1).abs() < f32::EPSILON);
+        for point in path.iter().skip(1) {
+            assert!(point.y.abs() < f32::EPSILON);
         }
     }
+
+    #[test]
+    fn project_path_burn_matches_engine() {
+        let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        rocket.throttle = 1.0;
+        let (path, _) = project_path(&rocket, &[], &[], &Bounds::Open, &PathRequest { burn: 1.0, duration: 0.5, steps: 30, start_time: 0.0 });
+        let mut flown = rocket.clone();
+        for _ in 0..30 {
+            apply_engine(&mut flown, 0.5 / 30.0);
+            move_rocket(&mut flown, 0.5 / 30.0);
+        }
+        let last = path.last().unwrap();
+        assert!((last.x - flown.x).abs() < 0.01);
+        assert!((last.y - flown.y).abs() < 0.01);
+        assert!(last.speed_x != 0.0 || last.speed_y != 0.0);
+    }
+
+    #[test]
+    fn project_path_burn_stops_after_duration() {
+        let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        rocket.throttle = 1.0;
+        let (path, _) = project_path(&rocket, &[], &[], &Bounds::Open, &PathRequest { burn: 0.5, duration: 1.0, steps: 60, start_time: 0.0 });
+        // coasting at constant velocity once the burn is over
+        assert_eq!(path[40].speed_x, path[59].speed_x);
+        assert_eq!(path[40].speed_y, path[59].speed_y);
+        assert!(path[10].speed_x.abs() + path[10].speed_y.abs() < path[40].speed_x.abs() + path[40].speed_y.abs());
+    }
+
+    #[test]
+    fn project_path_burn_stops_when_dry() {
+        let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        rocket.stages = vec![make_stage(0.005)];
+        rocket.throttle = 1.0;
+        // the stage only holds a fifth of a second of fuel
+        let (path, _) = project_path(&rocket, &[], &[], &Bounds::Open, &PathRequest { burn: f32::INFINITY, duration: 1.0, steps: 60, start_time: 0.0 });
+        assert_eq!(path[20].speed_y, path[59].speed_y);
+        assert!(path[20].speed_y != 0.0);
+    }
+
+    #[test]
+    fn predict_marks_burn_cutoff() {
+        let mut rocket = make_rocket(0.0, 0.0, 0.0, 0.0);
+        rocket.throttle = 0.5;
+        let level = make_level_state(rocket);
+        let burning = predict(&level, 0.0, 2.0, 1.0);
+        assert_eq!(burning.cutoff, Some(59));
+        assert!(predict(&level, 0.0, 2.0, 0.0).cutoff.is_none());
+        // a burn longer than the fuel lasts is cut short
+        let fuel_limited = predict(&level, 0.0, 50.0, f32::INFINITY);
+        let dry_at = burn_time(&level.rocket) / 0.5;
+        assert!((fuel_limited.time_at(fuel_limited.cutoff.unwrap()) - dry_at).abs() < 0.05);
+    }
+
+    // --- atmosphere tests ---
+
+    fn make_atmosphere_planet(x: f32, y: f32) -> Planet {
+        Planet {
+            x,
+            y,
+            speed_x: 0.0,
+            speed_y: 0.0,
+            radius: 30.0,
+            density: 1.0,
+            image: 0,
+            rotation: 0.0,
+            atmosphere: Some(Atmosphere { thickness: 50.0, density: 0.01, scale_height: 20.0 }),
+        }
+    }
+
+    #[test]
+    fn no_density_without_atmosphere() {
+        let rocket = make_rocket(40.0, 0.0, 0.0, 0.0);
+        assert!(air_density(&rocket, &[make_planet(0.0, 0.0)]).is_none());
+    }
+
+    #[test]
+    fn no_density_above_atmosphere() {
+        let rocket = make_rocket(81.0, 0.0, 0.0, 0.0);
+        assert!(air_density(&rocket, &[make_atmosphere_planet(0.0, 0.0)]).is_none());
+    }
+
+    #[test]
+    fn density_at_surface() {
+        let rocket = make_rocket(30.0, 0.0, 0.0, 0.0);
+        let (i, density) = air_density(&rocket, &[make_atmosphere_planet(0.0, 0.0)]).unwrap();
+        assert_eq!(i, 0);
+        assert!((density - 0.01).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn density_falls_with_altitude() {
+        let planets = [make_atmosphere_planet(0.0, 0.0)];
+        let (_, low) = air_density(&make_rocket(35.0, 0.0, 0.0, 0.0), &planets).unwrap();
+        let (_, high) = air_density(&make_rocket(70.0, 0.0, 0.0, 0.0), &planets).unwrap();
+        assert!(low > high);
+        // one scale height up the density drops by a factor of e
+        let (_, scale) = air_density(&make_rocket(50.0, 0.0, 0.0, 0.0), &planets).unwrap();
+        assert!((scale - 0.01 / std::f32::consts::E).abs() < 0.0001);
+    }
+
+    #[test]
+    fn drag_slows_rocket() {
+        let planets = [make_atmosphere_planet(0.0, 0.0)];
+        let mut rocket = make_rocket(40.0, 0.0, 0.0, 100.0);
+        let density = air_density(&rocket, &planets);
+        apply_drag(&mut rocket, &planets, density, 0.1);
+        assert_eq!(rocket.speed_x, 0.0);
+        assert!(rocket.speed_y > 0.0 && rocket.speed_y < 100.0);
+    }
+
+    #[test]
+    fn drag_is_quadratic() {
+        let planets = [make_atmosphere_planet(0.0, 0.0)];
+        let mut slow = make_rocket(40.0, 0.0, 0.0, 10.0);
+        let mut fast = make_rocket(40.0, 0.0, 0.0, 20.0);
+        let density = air_density(&slow, &planets);
+        apply_drag(&mut slow, &planets, density, 0.001);
+        apply_drag(&mut fast, &planets, density, 0.001);
+        // doubling the speed should roughly quadruple the speed lost
+        let ratio = (20.0 - fast.speed_y) / (10.0 - slow.speed_y);
+        assert!((ratio - 4.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn drag_never_reverses_velocity() {
+        let planets = [make_atmosphere_planet(0.0, 0.0)];
+        let mut rocket = make_rocket(30.0, 0.0, 0.0, 10000.0);
+        let density = air_density(&rocket, &planets);
+        apply_drag(&mut rocket, &planets, density, 1.0);
+        assert!(rocket.speed_y > 0.0);
+    }
+
+    #[test]
+    fn drag_relative_to_moving_atmosphere() {
+        let mut planet = make_atmosphere_planet(0.0, 0.0);
+        planet.speed_y = 50.0;
+        let planets = [planet];
+        let mut rocket = make_rocket(40.0, 0.0, 0.0, 50.0);
+        let density = air_density(&rocket, &planets);
+        apply_drag(&mut rocket, &planets, density, 1.0);
+        assert!((rocket.speed_y - 50.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn heating_when_fast_in_atmosphere() {
+        let planets = [make_atmosphere_planet(0.0, 0.0)];
+        let mut rocket = make_rocket(40.0, 0.0, 0.0, 300.0);
+        let density = air_density(&rocket, &planets);
+        apply_heating(&mut rocket, &planets, density, 0.1);
+        assert!(rocket.heat > 0.0);
+    }
+
+    #[test]
+    fn cools_down_outside_atmosphere() {
+        let planets = [make_atmosphere_planet(0.0, 0.0)];
+        let mut rocket = make_rocket(500.0, 0.0, 0.0, 300.0);
+        rocket.heat = 0.5;
+        let density = air_density(&rocket, &planets);
+        apply_heating(&mut rocket, &planets, density, 1.0);
+        assert!(rocket.heat < 0.5);
+        apply_heating(&mut rocket, &planets, density, 10.0);
+        assert_eq!(rocket.heat, 0.0);
+    }
+
+    #[test]
+    fn project_path_includes_drag() {
+        let rocket = make_rocket(0.0, -45.0, 100.0, 0.0);
+        let bare = vec![make_static_planet_def(0.0, 0.0, 30.0)];
+        let mut with_air = bare.clone();
+        with_air[0].atmosphere = Some(Atmosphere { thickness: 50.0, density: 0.01, scale_height: 20.0 });
+        let (bare_path, _) = project_path(&rocket, &bare, &[], &Bounds::Open, &PathRequest { burn: 0.0, duration: 0.1, steps: 10, start_time: 0.0 });
+        let (air_path, _) = project_path(&rocket, &with_air, &[], &Bounds::Open, &PathRequest { burn: 0.0, duration: 0.1, steps: 10, start_time: 0.0 });
+        // drag shortens the distance travelled along x
+        assert!(air_path.last().unwrap().x < bare_path.last().unwrap().x);
+    }
 }
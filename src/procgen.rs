@@ -0,0 +1,245 @@
+use macroquad::prelude::*;
+use crate::check;
+use crate::images::PLANET_IMAGES;
+use crate::rng::Rng;
+use crate::solver::{Options, Solver};
+use crate::state::{Bounds, Level, LevelState, Orbit, PlanetDef, Rocket, RotationMode, Stage, WinCondition};
+
+/// Size of a generated level, one screen like the first levels.
+const WORLD: Vec2 = vec2(1920.0, 1080.0);
+
+/// Gap kept between planet paths, the rocket's start and the goal.
+const CLEARANCE: f32 = 40.0;
+
+/// Tries at placing each planet before leaving it out.
+const PLACEMENT_TRIES: usize = 50;
+
+/// Solver runs spent on a layout before it's thrown away as too hard.
+const SOLVE_EVALUATIONS: usize = 1500;
+
+/// Seconds a generated level may take to win.
+const SOLVE_TIME: f32 = 60.0;
+
+/// Settings that make generated levels harder or easier.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Difficulty {
+    pub name: &'static str,
+    pub planets: usize,         // bodies to place, fewer if they don't fit
+    pub fuel: f32,              // fuel mass of the rocket's only stage
+    pub max_speed: Option<f32>, // speed limit in the goal, None for any speed
+}
+
+impl Difficulty {
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self { name: "Easy", planets: 2, fuel: 0.6, max_speed: None },
+            Self { name: "Normal", planets: 4, fuel: 0.4, max_speed: Some(150.0) },
+            Self { name: "Hard", planets: 6, fuel: 0.25, max_speed: Some(80.0) },
+        ]
+    }
+}
+
+/// Builds a level from `seed`, trying layouts until one has no design problems
+/// and the solver can win it. A few solver runs at a time, so the game can show
+/// progress while it works.
+#[derive(Clone)]
+pub struct Generator {
+    seed: u64,
+    difficulty: Difficulty,
+    rng: Rng,
+    attempts: usize,                // layouts tried so far
+    candidate: Option<(Level, Solver)>, // layout being checked for a win
+}
+
+impl Generator {
+    pub fn new(seed: u64, difficulty: Difficulty) -> Self {
+        Self { seed, difficulty, rng: Rng::new(seed), attempts: 0, candidate: None }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    /// Simulates up to `runs` solver runs, returning the level once one passes.
+    pub fn step(&mut self, runs: usize) -> Option<Level> {
+        if self.candidate.is_none() {
+            self.attempts += 1;
+            let level = layout(&mut self.rng, self.seed, &self.difficulty);
+            if !check::problems(&level).is_empty() {
+                return None;
+            }
+            let options = Options { max_time: SOLVE_TIME, evaluations: SOLVE_EVALUATIONS, ..Options::default() };
+            let solver = Solver::new(LevelState::new(level.clone()), options);
+            self.candidate = Some((level, solver));
+        }
+        let Some((level, solver)) = &mut self.candidate else { return None };
+        for _ in 0..runs {
+            if solver.finished() {
+                self.candidate = None;
+                return None;
+            }
+            solver.step(1);
+            // any win will do, the player finds their own way
+            if solver.solution().is_some() {
+                return Some(level.clone());
+            }
+        }
+        None
+    }
+}
+
+/// Generates the level for `seed` in one go, for tools and tests.
+pub fn generate(seed: u64, difficulty: Difficulty) -> Level {
+    let mut generator = Generator::new(seed, difficulty);
+    loop {
+        if let Some(level) = generator.step(SOLVE_EVALUATIONS) {
+            return level;
+        }
+    }
+}
+
+/// A random level: the rocket at rest on the left, the goal on the right and
+/// planets in between, static or orbiting a static one, all kept apart.
+fn layout(rng: &mut Rng, seed: u64, difficulty: &Difficulty) -> Level {
+    let start = vec2(rng.range(100.0, 400.0), rng.range(100.0, WORLD.y - 100.0));
+    let goal = vec2(rng.range(WORLD.x - 500.0, WORLD.x - 100.0), rng.range(100.0, WORLD.y - 100.0));
+    let goal_radius = 60.0;
+
+    let mut planets: Vec<PlanetDef> = Vec::new();
+    for _ in 0..difficulty.planets {
+        for _ in 0..PLACEMENT_TRIES {
+            let planet = random_planet(rng, &planets);
+            let clear_of_planets = planets
+                .iter()
+                .all(|other| check::closest_distance(&planet, other) >= planet.radius + other.radius + CLEARANCE);
+            let clear_of_start = check::closest_approach(check::path(&planet), (start, 0.0)) >= planet.radius + CLEARANCE;
+            let clear_of_goal =
+                check::closest_approach(check::path(&planet), (goal, 0.0)) >= planet.radius + goal_radius + CLEARANCE;
+            if clear_of_planets && clear_of_start && clear_of_goal {
+                planets.push(planet);
+                break;
+            }
+        }
+    }
+
+    let win_condition = match difficulty.max_speed {
+        Some(max_speed) => WinCondition::Circle { x: goal.x, y: goal.y, radius: goal_radius, max_speed },
+        None => WinCondition::CircleAnySpeed { x: goal.x, y: goal.y, radius: goal_radius },
+    };
+    Level {
+        name: "Endless",
+        planets,
+        initial_rocket: Rocket {
+            x: start.x,
+            y: start.y,
+            speed_x: 0.0,
+            speed_y: 0.0,
+            orientation: 90.0,
+            landed: false,
+            throttle: 0.0,
+            stages: vec![Stage { dry_mass: 0.5, fuel_mass: difficulty.fuel, thrust: 10.0, isp: 40.0 }],
+            heat: 0.0,
+            angular_velocity: 0.0,
+            rcs_fuel: 0.0,
+            strafe_x: 0.0,
+            strafe_y: 0.0,
+        },
+        win_condition,
+        rotation: RotationMode::Instant,
+        canisters: vec![],
+        depots: vec![],
+        hazards: vec![],
+        bounds: Bounds::Open,
+        world: WORLD,
+        star: None,
+        assists: true,
+        seed: Some(seed),
+        par: None,
+    }
+}
+
+/// A planet somewhere on screen, or now and then a moon of one already placed.
+fn random_planet(rng: &mut Rng, placed: &[PlanetDef]) -> PlanetDef {
+    let radius = rng.range(15.0, 50.0);
+    let parents: Vec<&PlanetDef> = placed.iter().filter(|planet| planet.orbit.is_none()).collect();
+    let (center, orbit) = if !parents.is_empty() && rng.next_f32() < 0.4 {
+        let parent = parents[rng.next_u64() as usize % parents.len()];
+        let direction = if rng.next_f32() < 0.5 { -1.0 } else { 1.0 };
+        let orbit = Orbit {
+            radius: parent.radius + radius + rng.range(CLEARANCE, 150.0),
+            speed: direction * rng.range(0.2, 0.6),
+            initial_angle: rng.range(0.0, std::f32::consts::TAU),
+        };
+        (vec2(parent.center_x, parent.center_y), Some(orbit))
+    } else {
+        (vec2(rng.range(300.0, WORLD.x - 300.0), rng.range(100.0, WORLD.y - 100.0)), None)
+    };
+    PlanetDef {
+        center_x: center.x,
+        center_y: center.y,
+        radius,
+        density: rng.range(0.8, 1.5),
+        image: rng.next_u64() as usize % PLANET_IMAGES,
+        spin: rng.range(-15.0, 15.0),
+        orbit,
+        atmosphere: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn planet_positions(level: &Level) -> Vec<(f32, f32, f32)> {
+        level.planets.iter().map(|planet| (planet.center_x, planet.center_y, planet.radius)).collect()
+    }
+
+    #[test]
+    fn same_seed_same_layout() {
+        let difficulty = Difficulty::all()[1];
+        let a = layout(&mut Rng::new(7), 7, &difficulty);
+        let b = layout(&mut Rng::new(7), 7, &difficulty);
+        assert_eq!(planet_positions(&a), planet_positions(&b));
+        let c = layout(&mut Rng::new(8), 8, &difficulty);
+        assert_ne!(planet_positions(&a), planet_positions(&c));
+    }
+
+    #[test]
+    fn layouts_keep_their_distance() {
+        for difficulty in Difficulty::all() {
+            for seed in 0..50 {
+                let level = layout(&mut Rng::new(seed), seed, &difficulty);
+                assert_eq!(check::problems(&level), Vec::<String>::new(), "{} seed {}", difficulty.name, seed);
+                assert!(level.planets.len() <= difficulty.planets);
+            }
+        }
+    }
+
+    #[test]
+    fn difficulty_sets_fuel_and_goal() {
+        let hard = Difficulty::all()[2];
+        let level = layout(&mut Rng::new(3), 3, &hard);
+        assert_eq!(level.initial_rocket.fuel(), hard.fuel);
+        assert!(matches!(level.win_condition, WinCondition::Circle { max_speed, .. } if max_speed == 80.0));
+        assert_eq!(level.seed, Some(3));
+    }
+
+    #[test]
+    fn generator_returns_a_winnable_level() {
+        let mut generator = Generator::new(1, Difficulty::all()[0]);
+        let level = loop {
+            if let Some(level) = generator.step(100) {
+                break level;
+            }
+        };
+        assert_eq!(level.seed, Some(1));
+        // the solver found a win on the layout it handed back
+        let (_, solver) = generator.candidate.as_ref().expect("the winning layout is kept");
+        assert!(solver.solution().is_some());
+        assert_eq!(planet_positions(&generate(1, Difficulty::all()[0])), planet_positions(&level));
+    }
+}
@@ -0,0 +1,130 @@
+/// What a win earns against the level's par time.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Medal {
+    Gold,   // at or under par
+    Silver, // within half as long again
+    Bronze,
+}
+
+impl Medal {
+    pub fn for_time(time: f32, par: f32) -> Self {
+        if time <= par {
+            Medal::Gold
+        } else if time <= par * 1.5 {
+            Medal::Silver
+        } else {
+            Medal::Bronze
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Medal::Gold => "Gold",
+            Medal::Silver => "Silver",
+            Medal::Bronze => "Bronze",
+        }
+    }
+}
+
+/// What the player has achieved and picked, kept in `quad_storage` between sessions.
+#[derive(Clone)]
+pub struct Progress {
+    pub completed: Vec<String>,         // names of the levels won at least once
+    pub best_times: Vec<(String, f32)>, // fastest win per level, in seconds
+    pub skin: usize,                    // index into `skins::all()`
+}
+
+impl Progress {
+    pub fn load() -> Self {
+        let storage = quad_storage::STORAGE.lock().unwrap();
+        let completed = storage.get("completed_levels").map_or_else(Vec::new, |v| parse_list(&v));
+        let best_times = storage.get("best_times").map_or_else(Vec::new, |v| parse_times(&v));
+        let skin = storage.get("rocket_skin").and_then(|v| v.parse().ok()).unwrap_or(0);
+        Self { completed, best_times, skin }
+    }
+
+    /// Records a win that took `time` seconds, if it was timed.
+    pub fn complete_level(&mut self, name: &str, time: Option<f32>) {
+        let mut storage = quad_storage::STORAGE.lock().unwrap();
+        if !self.completed.iter().any(|c| c == name) {
+            self.completed.push(name.to_string());
+            storage.set("completed_levels", &self.completed.join("\n"));
+        }
+        if let Some(time) = time && record_time(&mut self.best_times, name, time) {
+            let lines: Vec<String> = self.best_times.iter().map(|(name, time)| format!("{}\t{}", name, time)).collect();
+            storage.set("best_times", &lines.join("\n"));
+        }
+    }
+
+    pub fn best_time(&self, name: &str) -> Option<f32> {
+        self.best_times.iter().find(|(level, _)| level == name).map(|(_, time)| *time)
+    }
+
+    pub fn select_skin(&mut self, skin: usize) {
+        self.skin = skin;
+        let mut storage = quad_storage::STORAGE.lock().unwrap();
+        storage.set("rocket_skin", &skin.to_string());
+    }
+}
+
+/// Level names can contain anything but line breaks, so they're stored one per line.
+fn parse_list(value: &str) -> Vec<String> {
+    value.lines().filter(|line| !line.is_empty()).map(str::to_string).collect()
+}
+
+/// One `name<TAB>seconds` per line, split at the last tab in case a name has one.
+fn parse_times(value: &str) -> Vec<(String, f32)> {
+    value
+        .lines()
+        .filter_map(|line| line.rsplit_once('\t'))
+        .filter_map(|(name, time)| Some((name.to_string(), time.parse().ok()?)))
+        .collect()
+}
+
+/// Keeps `time` if it's the level's first or fastest, returning whether it was.
+fn record_time(times: &mut Vec<(String, f32)>, name: &str, time: f32) -> bool {
+    match times.iter_mut().find(|(level, _)| level == name) {
+        Some((_, best)) if *best <= time => false,
+        Some((_, best)) => {
+            *best = time;
+            true
+        }
+        None => {
+            times.push((name.to_string(), time));
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_stored_list() {
+        assert_eq!(parse_list("Level 1\nThe Gauntlet\n"), vec!["Level 1", "The Gauntlet"]);
+        assert!(parse_list("").is_empty());
+    }
+
+    #[test]
+    fn parses_stored_times() {
+        let times = parse_times("Level 1\t12.5\nbroken\nThe Gauntlet\tfast\nPinball\t30\n");
+        assert_eq!(times, vec![("Level 1".to_string(), 12.5), ("Pinball".to_string(), 30.0)]);
+    }
+
+    #[test]
+    fn keeps_the_fastest_time() {
+        let mut times = Vec::new();
+        assert!(record_time(&mut times, "Level 1", 20.0));
+        assert!(!record_time(&mut times, "Level 1", 25.0));
+        assert!(record_time(&mut times, "Level 1", 15.0));
+        assert_eq!(times, vec![("Level 1".to_string(), 15.0)]);
+    }
+
+    #[test]
+    fn medals_against_par() {
+        assert_eq!(Medal::for_time(10.0, 10.0), Medal::Gold);
+        assert_eq!(Medal::for_time(14.0, 10.0), Medal::Silver);
+        assert_eq!(Medal::for_time(16.0, 10.0), Medal::Bronze);
+    }
+}
@@ -0,0 +1,100 @@
+use macroquad::prelude::*;
+use crate::progress::Progress;
+
+/// Size of one frame in `assets/rockets/rockets.png`; frames sit side by side.
+pub const FRAME_SIZE: Vec2 = vec2(24.0, 80.0);
+
+/// World pixels per atlas pixel.
+pub const SCALE: f32 = 0.5;
+
+#[derive(Clone)]
+pub enum Unlock {
+    Always,
+    LevelsCompleted(usize),
+    Level(&'static str),
+}
+
+#[derive(Clone)]
+pub struct Skin {
+    pub name: &'static str,
+    pub frame: usize,
+    pub pivot: Vec2,        // atlas pixel at the rocket's position, the middle of the hull's base
+    pub flame_anchor: Vec2, // atlas pixel the exhaust leaves from
+    pub unlock: Unlock,
+}
+
+impl Skin {
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self { name: "Classic", frame: 0, pivot: vec2(12.0, 76.0), flame_anchor: vec2(12.0, 76.0), unlock: Unlock::Always },
+            Self { name: "Retro", frame: 1, pivot: vec2(12.0, 72.0), flame_anchor: vec2(12.0, 80.0), unlock: Unlock::LevelsCompleted(3) },
+            Self { name: "Shuttle", frame: 2, pivot: vec2(12.0, 74.0), flame_anchor: vec2(12.0, 78.0), unlock: Unlock::Level("Aerobrake") },
+            Self { name: "Gold", frame: 3, pivot: vec2(12.0, 76.0), flame_anchor: vec2(12.0, 76.0), unlock: Unlock::LevelsCompleted(10) },
+        ]
+    }
+
+    pub fn unlocked(&self, progress: &Progress) -> bool {
+        match self.unlock {
+            Unlock::Always => true,
+            Unlock::LevelsCompleted(count) => progress.completed.len() >= count,
+            Unlock::Level(name) => progress.completed.iter().any(|c| c == name),
+        }
+    }
+
+    pub fn requirement(&self) -> String {
+        match self.unlock {
+            Unlock::Always => String::new(),
+            Unlock::LevelsCompleted(count) => format!("complete {} levels", count),
+            Unlock::Level(name) => format!("complete {}", name),
+        }
+    }
+
+    /// Where the exhaust leaves relative to the rocket's position, in world pixels
+    /// with the rocket pointing up.
+    pub fn flame_offset(&self) -> Vec2 {
+        (self.flame_anchor - self.pivot) * SCALE
+    }
+
+    pub fn source_rect(&self) -> Rect {
+        Rect::new(self.frame as f32 * FRAME_SIZE.x, 0.0, FRAME_SIZE.x, FRAME_SIZE.y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(completed: &[&str]) -> Progress {
+        Progress { completed: completed.iter().map(|c| c.to_string()).collect(), best_times: Vec::new(), skin: 0 }
+    }
+
+    #[test]
+    fn first_skin_is_always_available() {
+        assert!(Skin::all()[0].unlocked(&progress(&[])));
+    }
+
+    #[test]
+    fn skins_unlock_with_completions() {
+        let retro = &Skin::all()[1];
+        assert!(!retro.unlocked(&progress(&["Level 1", "Level 2"])));
+        assert!(retro.unlocked(&progress(&["Level 1", "Level 2", "Slingshot"])));
+        let shuttle = &Skin::all()[2];
+        assert!(!shuttle.unlocked(&progress(&["Level 1"])));
+        assert!(shuttle.unlocked(&progress(&["Aerobrake"])));
+    }
+
+    #[test]
+    fn frames_are_laid_out_in_a_row() {
+        for (i, skin) in Skin::all().iter().enumerate() {
+            assert_eq!(skin.frame, i);
+            assert_eq!(skin.source_rect().x, i as f32 * FRAME_SIZE.x);
+        }
+    }
+
+    #[test]
+    fn flame_offset_points_down_from_pivot() {
+        let retro = &Skin::all()[1];
+        assert_eq!(retro.flame_offset(), vec2(0.0, 4.0));
+        assert_eq!(Skin::all()[0].flame_offset(), Vec2::ZERO);
+    }
+}
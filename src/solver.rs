@@ -0,0 +1,493 @@
+use macroquad::prelude::*;
+use crate::controls;
+use crate::physics::{self, PhysicsEvent};
+use crate::rng::Rng;
+use crate::state::{LevelState, RotationMode, WinCondition};
+
+/// Time step of simulated runs, one frame at 60 fps. The autopilot flies plans
+/// in the same steps, as a plan only wins when flown the way it was found.
+pub const DT: f32 = 1.0 / 60.0;
+
+/// Added to the cost of runs that don't win, so any win beats any miss.
+const UNSOLVED: f64 = 1.0e6;
+
+/// Runs without an improvement before the search gives up on a plan and starts
+/// again from a random one.
+const RESTART_AFTER: usize = 100;
+
+/// Runs spent looking for a better win after the first one is found.
+const POLISH: usize = 2000;
+
+/// One burn of a plan: from `start` seconds into the run, fire the engine at full
+/// throttle for `duration` seconds while holding `heading`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Burn {
+    pub start: f32,
+    pub heading: f32, // degrees clockwise from up, like `Rocket::orientation`
+    pub duration: f32,
+}
+
+/// A run to fly: burns in order of their start, coasting in between. While
+/// coasting the rocket already turns towards the next burn.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Plan {
+    pub burns: Vec<Burn>,
+}
+
+impl Plan {
+    /// Heading to hold and whether to burn at `time` seconds into the run.
+    pub fn control(&self, time: f32) -> (Option<f32>, bool) {
+        for burn in &self.burns {
+            if time < burn.start {
+                return (Some(burn.heading), false);
+            }
+            if time < burn.start + burn.duration {
+                return (Some(burn.heading), true);
+            }
+        }
+        (None, false)
+    }
+
+    /// The same plan flown up to `end` seconds, without the burns that never
+    /// fire and with overlaps cut so each burn starts when the one before ends.
+    pub fn simplified(&self, end: f32) -> Plan {
+        let mut burns: Vec<Burn> = Vec::new();
+        let mut previous_end = 0.0f32;
+        for burn in &self.burns {
+            let start = burn.start.max(previous_end);
+            let stop = burn.start + burn.duration;
+            if start >= end {
+                break;
+            }
+            if stop <= previous_end {
+                continue;
+            }
+            burns.push(Burn { start, heading: burn.heading, duration: stop - start });
+            previous_end = stop;
+        }
+        // turning towards a burn that never comes changes nothing
+        while burns.last().is_some_and(|burn| burn.duration == 0.0) {
+            burns.pop();
+        }
+        Plan { burns }
+    }
+}
+
+/// How a simulated run went.
+#[derive(Clone, Debug)]
+pub struct Outcome {
+    pub won: bool,
+    pub time: f32, // seconds until the win, or until the run ended
+    pub burn: f32, // seconds the engine was firing
+    pub miss: f32, // how far the run stayed from winning at its closest, 0 for a win
+    pub path: Vec<Vec2>, // where the rocket went, only kept for the best win
+}
+
+/// What the search optimises among the runs that win.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Objective {
+    Fuel,
+    Time,
+}
+
+#[derive(Clone)]
+pub struct Options {
+    pub objective: Objective,
+    pub max_time: f32,      // seconds a run may take before it counts as a miss
+    pub max_burns: usize,   // most burns in a plan
+    pub evaluations: usize, // runs to simulate in total
+    pub seed: u64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self { objective: Objective::Fuel, max_time: 120.0, max_burns: 3, evaluations: 10000, seed: 0 }
+    }
+}
+
+/// Turns the rocket and sets the throttle as `plan` says for `time` seconds into
+/// the run, staging when the active stage runs dry. Flies both the simulated
+/// runs and the autopilot.
+pub fn steer(level: &mut LevelState, plan: &Plan, time: f32, dt: f32) {
+    let (heading, burning) = plan.control(time);
+    let rocket = &mut level.rocket;
+    match (level.level.rotation, heading) {
+        (RotationMode::Instant, Some(heading)) => controls::hold_orientation(rocket, dt, heading),
+        (RotationMode::Instant, None) => {}
+        (RotationMode::Momentum, Some(heading)) => controls::hold_angular_velocity(rocket, dt, heading),
+        (RotationMode::Momentum, None) => controls::update_angular_velocity(rocket, dt, false, false, true),
+    }
+    if burning && rocket.fuel() <= 0.0 {
+        physics::jettison_stage(level);
+    }
+    let rocket = &mut level.rocket;
+    rocket.throttle = if burning && rocket.fuel() > 0.0 { 1.0 } else { 0.0 };
+    rocket.strafe_x = 0.0;
+    rocket.strafe_y = 0.0;
+}
+
+/// Flies `plan` from `start` with the game's own physics until the rocket wins,
+/// crashes or runs out of time.
+pub fn simulate(start: &LevelState, plan: &Plan, max_time: f32) -> Outcome {
+    fly(start, plan, max_time, false)
+}
+
+/// Like `simulate`, also recording where the rocket went, for showing a plan.
+pub fn simulate_path(start: &LevelState, plan: &Plan, max_time: f32) -> Outcome {
+    fly(start, plan, max_time, true)
+}
+
+fn fly(start: &LevelState, plan: &Plan, max_time: f32, record: bool) -> Outcome {
+    let mut level = start.clone();
+    let mut outcome = Outcome { won: false, time: 0.0, burn: 0.0, miss: f32::INFINITY, path: Vec::new() };
+    let mut steps = 0;
+    while outcome.time < max_time {
+        steer(&mut level, plan, outcome.time, DT);
+        if level.rocket.throttle > 0.0 {
+            outcome.burn += DT;
+        }
+        let event = physics::step(&mut level, DT);
+        // counted in whole steps, so the autopilot can tell the time the same way
+        steps += 1;
+        outcome.time = steps as f32 * DT;
+        if record {
+            outcome.path.push(vec2(level.rocket.x, level.rocket.y));
+        }
+        // a win needs the engine off, so only coasting counts as getting close
+        if level.rocket.throttle == 0.0 {
+            outcome.miss = outcome.miss.min(miss(&level));
+        }
+        match event {
+            Some(PhysicsEvent::Win) => {
+                outcome.won = true;
+                outcome.miss = 0.0;
+                break;
+            }
+            Some(_) => break,
+            None => {}
+        }
+    }
+    outcome
+}
+
+/// How far the rocket is from winning: the distance to the goal circle plus any
+/// speed over its limit.
+fn miss(level: &LevelState) -> f32 {
+    let rocket = &level.rocket;
+    let condition = &level.level.win_condition;
+    let (distance, _) = physics::goal_approach(rocket, condition);
+    let excess = match condition {
+        WinCondition::Circle { max_speed, .. } => {
+            let speed = (rocket.speed_x * rocket.speed_x + rocket.speed_y * rocket.speed_y).sqrt();
+            (speed - max_speed).max(0.0)
+        }
+        WinCondition::CircleAnySpeed { .. } => 0.0,
+    };
+    distance.max(0.0) + excess
+}
+
+/// Searches for a plan that wins the level by hill climbing: small changes to a
+/// plan are kept when they get closer to winning, and when that stalls it starts
+/// over from a random plan. Runs a few simulations at a time so the game can
+/// spread the search over frames.
+#[derive(Clone)]
+pub struct Solver {
+    start: LevelState,
+    options: Options,
+    rng: Rng,
+    fuel_time: f32, // seconds of full burn in the active stage, counting the canisters
+    best: Option<(Plan, Outcome, f64)>,
+    current: Option<(Plan, f64)>, // plan being climbed from
+    stale: usize,                 // runs since `current` last improved
+    solved_at: Option<usize>,     // runs it took to find the first win
+    evaluations: usize,
+}
+
+impl Solver {
+    pub fn new(start: LevelState, options: Options) -> Self {
+        // a search needs at least one run, with at least one burn to try
+        let options = Options { evaluations: options.evaluations.max(1), max_burns: options.max_burns.max(1), ..options };
+        let rocket = &start.rocket;
+        let canisters: f32 = start.level.canisters.iter().map(|canister| canister.fuel_mass).sum();
+        let burn_per_fuel = if rocket.fuel() > 0.0 { physics::burn_time(rocket) / rocket.fuel() } else { 0.0 };
+        let fuel_time = ((rocket.fuel() + canisters) * burn_per_fuel).max(0.5);
+        let rng = Rng::new(options.seed);
+        Self { start, options, rng, fuel_time, best: None, current: None, stale: 0, solved_at: None, evaluations: 0 }
+    }
+
+    /// Done when out of runs, or a while after the first win was found.
+    pub fn finished(&self) -> bool {
+        let polished = self.solved_at.is_some_and(|at| self.evaluations >= at + POLISH);
+        self.evaluations >= self.options.evaluations || polished
+    }
+
+    /// Share of the search done, from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        let total = self.solved_at.map_or(self.options.evaluations, |at| (at + POLISH).min(self.options.evaluations));
+        self.evaluations as f32 / total as f32
+    }
+
+    /// Level time the plan's times count from.
+    pub fn start_time(&self) -> f64 {
+        self.start.time
+    }
+
+    /// The best winning plan found so far.
+    pub fn solution(&self) -> Option<(&Plan, &Outcome)> {
+        self.best.as_ref().filter(|(_, outcome, _)| outcome.won).map(|(plan, outcome, _)| (plan, outcome))
+    }
+
+    /// Simulates up to `count` more runs.
+    pub fn step(&mut self, count: usize) {
+        for _ in 0..count {
+            if self.finished() {
+                return;
+            }
+            let plan = self.candidate();
+            self.evaluations += 1;
+            let outcome = simulate(&self.start, &plan, self.options.max_time);
+            let cost = self.cost(&outcome);
+            let plan = plan.simplified(outcome.time);
+            if outcome.won && self.solved_at.is_none() {
+                self.solved_at = Some(self.evaluations);
+            }
+            if self.current.as_ref().is_none_or(|(_, current)| cost < *current) {
+                self.current = Some((plan.clone(), cost));
+                self.stale = 0;
+            } else {
+                self.stale += 1;
+            }
+            if self.best.as_ref().is_none_or(|(_, _, best)| cost < *best) {
+                // only a win gets shown, so only a win is flown again to see its path
+                let mut outcome = outcome;
+                if outcome.won {
+                    outcome.path = simulate_path(&self.start, &plan, self.options.max_time).path;
+                }
+                self.best = Some((plan, outcome, cost));
+            }
+        }
+    }
+
+    fn cost(&self, outcome: &Outcome) -> f64 {
+        if !outcome.won {
+            return UNSOLVED + outcome.miss as f64;
+        }
+        // the other measure breaks ties between equally good runs
+        let cost = match self.options.objective {
+            Objective::Fuel => outcome.burn + outcome.time * 0.01,
+            Objective::Time => outcome.time + outcome.burn * 0.01,
+        };
+        cost as f64
+    }
+
+    fn candidate(&mut self) -> Plan {
+        if self.evaluations == 0 {
+            // some levels only need a coast
+            return Plan::default();
+        }
+        if self.stale >= RESTART_AFTER {
+            self.current = None;
+        }
+        match &self.current {
+            Some((plan, _)) => {
+                let plan = plan.clone();
+                self.mutate(plan)
+            }
+            None => self.random_plan(),
+        }
+    }
+
+    fn random_burn(&mut self) -> Burn {
+        Burn {
+            start: self.rng.range(0.0, self.options.max_time * 0.5),
+            heading: self.rng.range(0.0, 360.0),
+            duration: self.rng.range(0.0, self.fuel_time),
+        }
+    }
+
+    fn random_plan(&mut self) -> Plan {
+        let count = 1 + (self.rng.next_u64() % self.options.max_burns as u64) as usize;
+        let mut burns: Vec<Burn> = (0..count).map(|_| self.random_burn()).collect();
+        burns.sort_by(|a, b| a.start.total_cmp(&b.start));
+        Plan { burns }
+    }
+
+    /// Nudges one burn, or now and then adds or drops a burn.
+    fn mutate(&mut self, mut plan: Plan) -> Plan {
+        let roll = self.rng.next_f32();
+        if plan.burns.is_empty() || (roll < 0.05 && plan.burns.len() < self.options.max_burns) {
+            let burn = self.random_burn();
+            plan.burns.push(burn);
+        } else if roll < 0.1 {
+            let i = self.rng.next_u64() as usize % plan.burns.len();
+            plan.burns.remove(i);
+        } else {
+            // steps of every size from large to very fine, as close passes need precision
+            let scale = 10f32.powf(-self.rng.range(0.0, 3.0));
+            let i = self.rng.next_u64() as usize % plan.burns.len();
+            let start = self.rng.range(-2.0, 2.0) * scale;
+            let heading = self.rng.range(-30.0, 30.0) * scale;
+            let duration = self.rng.range(-0.3, 0.3) * self.fuel_time * scale;
+            let burn = &mut plan.burns[i];
+            burn.start = (burn.start + start).max(0.0);
+            burn.heading = (burn.heading + heading).rem_euclid(360.0);
+            burn.duration = (burn.duration + duration).max(0.0);
+        }
+        plan.burns.sort_by(|a, b| a.start.total_cmp(&b.start));
+        plan
+    }
+}
+
+/// Runs the whole search and returns the best plan found. Check `Outcome::won`
+/// to see whether it actually wins.
+pub fn solve(start: &LevelState, options: Options) -> (Plan, Outcome) {
+    let mut solver = Solver::new(start.clone(), options);
+    solver.step(solver.options.evaluations);
+    let (plan, outcome, _) = solver.best.expect("at least one run is simulated");
+    (plan, outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{Bounds, Level, Rocket, Stage};
+
+    fn make_level(speed_y: f32, goal_y: f32) -> LevelState {
+        let rocket = Rocket {
+            x: 100.0,
+            y: 500.0,
+            speed_x: 0.0,
+            speed_y,
+            orientation: 0.0,
+            landed: false,
+            throttle: 0.0,
+            stages: vec![Stage { dry_mass: 0.5, fuel_mass: 0.5, thrust: 20.0, isp: 40.0 }],
+            heat: 0.0,
+            angular_velocity: 0.0,
+            rcs_fuel: 0.0,
+            strafe_x: 0.0,
+            strafe_y: 0.0,
+        };
+        LevelState::new(Level {
+            name: "Test",
+            planets: vec![],
+            initial_rocket: rocket,
+            win_condition: WinCondition::CircleAnySpeed { x: 100.0, y: goal_y, radius: 20.0 },
+            rotation: RotationMode::Instant,
+            canisters: vec![],
+            depots: vec![],
+            hazards: vec![],
+            bounds: Bounds::Open,
+            world: vec2(200.0, 600.0),
+            star: None,
+            assists: true,
+            seed: None,
+            par: None,
+        })
+    }
+
+    fn options() -> Options {
+        Options { max_time: 5.0, evaluations: 200, ..Default::default() }
+    }
+
+    #[test]
+    fn control_follows_burns_in_order() {
+        let plan = Plan {
+            burns: vec![
+                Burn { start: 1.0, heading: 90.0, duration: 2.0 },
+                Burn { start: 5.0, heading: 180.0, duration: 1.0 },
+            ],
+        };
+        assert_eq!(plan.control(0.5), (Some(90.0), false));
+        assert_eq!(plan.control(1.5), (Some(90.0), true));
+        assert_eq!(plan.control(4.0), (Some(180.0), false));
+        assert_eq!(plan.control(5.5), (Some(180.0), true));
+        assert_eq!(plan.control(7.0), (None, false));
+    }
+
+    #[test]
+    fn simplified_plan_flies_the_same() {
+        let plan = Plan {
+            burns: vec![
+                Burn { start: 0.5, heading: 90.0, duration: 1.0 },
+                Burn { start: 1.0, heading: 0.0, duration: 1.0 },
+                Burn { start: 1.2, heading: 45.0, duration: 0.5 },
+                Burn { start: 2.5, heading: 180.0, duration: 0.0 },
+                Burn { start: 9.0, heading: 270.0, duration: 1.0 },
+            ],
+        };
+        let simple = plan.simplified(5.0);
+        assert_eq!(simple.burns, vec![
+            Burn { start: 0.5, heading: 90.0, duration: 1.0 },
+            Burn { start: 1.5, heading: 0.0, duration: 0.5 },
+        ]);
+        for i in 0..300 {
+            let time = i as f32 * 0.01;
+            assert_eq!(simple.control(time).1, plan.control(time).1);
+        }
+    }
+
+    #[test]
+    fn coasting_into_goal_wins() {
+        let outcome = simulate(&make_level(-100.0, 300.0), &Plan::default(), 5.0);
+        assert!(outcome.won);
+        assert_eq!(outcome.burn, 0.0);
+        assert!(outcome.time > 1.5 && outcome.time < 2.0);
+    }
+
+    #[test]
+    fn miss_measures_closest_approach() {
+        // drifting straight past the side of the goal
+        let level = make_level(-100.0, 300.0);
+        let mut off_center = level.clone();
+        off_center.rocket.x = 160.0;
+        let outcome = simulate(&off_center, &Plan::default(), 5.0);
+        assert!(!outcome.won);
+        assert!((outcome.miss - 40.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn finds_a_burn_towards_the_goal() {
+        let (plan, outcome) = solve(&make_level(0.0, 300.0), options());
+        assert!(outcome.won);
+        assert!(!plan.burns.is_empty());
+    }
+
+    #[test]
+    fn only_the_winning_run_records_its_path() {
+        let level = make_level(0.0, 300.0);
+        let (plan, outcome) = solve(&level, options());
+        assert!(outcome.won);
+        let path = &outcome.path;
+        assert!((path.len() as f32 * DT - outcome.time).abs() < DT);
+        assert!(path.last().unwrap().distance(vec2(100.0, 300.0)) < 20.0);
+        assert!(simulate(&level, &plan, 5.0).path.is_empty());
+    }
+
+    #[test]
+    fn same_seed_same_solution() {
+        let a = solve(&make_level(0.0, 300.0), options());
+        let b = solve(&make_level(0.0, 300.0), options());
+        assert_eq!(a.0, b.0);
+    }
+
+    #[test]
+    fn empty_search_still_runs_once() {
+        let options = Options { evaluations: 0, max_burns: 0, ..options() };
+        let solver = Solver::new(make_level(0.0, 300.0), options.clone());
+        assert_eq!(solver.progress(), 0.0);
+        let (_, outcome) = solve(&make_level(-100.0, 300.0), options);
+        assert!(outcome.won);
+    }
+
+    #[test]
+    fn search_can_be_spread_over_steps() {
+        let mut solver = Solver::new(make_level(-100.0, 300.0), options());
+        solver.step(1);
+        assert_eq!(solver.progress(), 1.0 / 200.0);
+        assert!(solver.solution().is_some());
+        assert!(!solver.finished());
+        solver.step(1000);
+        assert!(solver.finished());
+    }
+}
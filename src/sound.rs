@@ -16,25 +16,27 @@ pub struct Sounds {
 
 impl Sounds {
     pub async fn load() -> Self {
-        let mut storage = quad_storage::STORAGE.lock().unwrap();
+        let (music_volume, effect_volume) = {
+            let mut storage = quad_storage::STORAGE.lock().unwrap();
 
-        let music_volume = match storage.get("music_volume") {
-            Some(v) => v.parse::<f32>().unwrap_or(0.5),
-            None => {
-                storage.set("music_volume", "0.5");
-                0.5
-            }
-        };
+            let music_volume = match storage.get("music_volume") {
+                Some(v) => v.parse::<f32>().unwrap_or(0.5),
+                None => {
+                    storage.set("music_volume", "0.5");
+                    0.5
+                }
+            };
 
-        let effect_volume = match storage.get("effect_volume") {
-            Some(v) => v.parse::<f32>().unwrap_or(1.0),
-            None => {
-                storage.set("effect_volume", "1");
-                1.0
-            }
-        };
+            let effect_volume = match storage.get("effect_volume") {
+                Some(v) => v.parse::<f32>().unwrap_or(1.0),
+                None => {
+                    storage.set("effect_volume", "1");
+                    1.0
+                }
+            };
 
-        drop(storage);
+            (music_volume, effect_volume)
+        };
 
         let sfx = "assets/sounds";
         Self {
@@ -72,10 +74,10 @@ impl Sounds {
             self.motor_playing = false;
         }
 
-        if rocket.engine_on && !self.engine_playing {
+        if rocket.throttle > 0.0 && !self.engine_playing {
             play_sound(&self.engine_fire, PlaySoundParams { looped: true, volume: 0.3 * self.effect_volume });
             self.engine_playing = true;
-        } else if !rocket.engine_on && self.engine_playing {
+        } else if rocket.throttle == 0.0 && self.engine_playing {
             stop_sound(&self.engine_fire);
             self.engine_playing = false;
         }
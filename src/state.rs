@@ -1,11 +1,22 @@
 use macroquad::prelude::*;
+use crate::camera::Camera;
+use crate::daily::Replay;
+use crate::hazards::{self, Asteroid, HazardDef, SpatialGrid};
+use crate::particles::Particles;
+use crate::skins::Skin;
+use crate::solver::Solver;
 
 #[derive(Clone)]
 pub struct Planet {
     pub x: f32,
     pub y: f32,
+    pub speed_x: f32,
+    pub speed_y: f32,
     pub radius: f32,
+    pub density: f32,
     pub image: usize,
+    pub rotation: f32, // degrees
+    pub atmosphere: Option<Atmosphere>,
 }
 
 #[derive(Clone)]
@@ -15,37 +26,88 @@ pub struct Orbit {
     pub initial_angle: f32, // radians
 }
 
+impl Orbit {
+    /// Position relative to the orbit center at the given time.
+    pub fn offset_at(&self, time: f64) -> (f32, f32) {
+        let angle = self.initial_angle + self.speed * time as f32;
+        (self.radius * angle.cos(), self.radius * angle.sin())
+    }
+
+    pub fn velocity_at(&self, time: f64) -> (f32, f32) {
+        let angle = self.initial_angle + self.speed * time as f32;
+        (-self.radius * self.speed * angle.sin(), self.radius * self.speed * angle.cos())
+    }
+}
+
+#[derive(Clone)]
+pub struct Atmosphere {
+    pub thickness: f32,    // height of the atmosphere above the surface
+    pub density: f32,      // drag factor at the surface
+    pub scale_height: f32, // altitude over which density falls by a factor of e
+}
+
 #[derive(Clone)]
 pub struct PlanetDef {
     pub center_x: f32,      // static position, or orbit center
     pub center_y: f32,
     pub radius: f32,        // planet body radius
-    pub image: usize,       // index into planet_textures (0-9)
+    pub density: f32,       // 1.0 = normal; mass scales with density * radius^3
+    pub image: usize,       // index into planet_textures, below `images::PLANET_IMAGES`
+    pub spin: f32,          // degrees per second, only for looks
     pub orbit: Option<Orbit>,
+    pub atmosphere: Option<Atmosphere>,
 }
 
 impl PlanetDef {
     pub fn planet_at(&self, time: f64) -> Planet {
-        match &self.orbit {
-            None => Planet {
-                x: self.center_x,
-                y: self.center_y,
-                radius: self.radius,
-                image: self.image,
-            },
-            Some(orbit) => {
-                let angle = orbit.initial_angle + orbit.speed * time as f32;
-                Planet {
-                    x: self.center_x + orbit.radius * angle.cos(),
-                    y: self.center_y + orbit.radius * angle.sin(),
-                    radius: self.radius,
-                    image: self.image,
-                }
-            }
+        let (dx, dy) = self.orbit.as_ref().map_or((0.0, 0.0), |orbit| orbit.offset_at(time));
+        let (speed_x, speed_y) = self.orbit.as_ref().map_or((0.0, 0.0), |orbit| orbit.velocity_at(time));
+        Planet {
+            x: self.center_x + dx,
+            y: self.center_y + dy,
+            speed_x,
+            speed_y,
+            radius: self.radius,
+            density: self.density,
+            image: self.image,
+            rotation: (self.spin as f64 * time).rem_euclid(360.0) as f32,
+            atmosphere: self.atmosphere.clone(),
         }
     }
 }
 
+/// A floating fuel canister; flying through it adds its fuel to the active stage.
+#[derive(Clone)]
+pub struct FuelCanister {
+    pub center_x: f32, // static position, or orbit center
+    pub center_y: f32,
+    pub orbit: Option<Orbit>,
+    pub fuel_mass: f32,
+}
+
+impl FuelCanister {
+    pub fn position_at(&self, time: f64) -> (f32, f32) {
+        let (dx, dy) = self.orbit.as_ref().map_or((0.0, 0.0), |orbit| orbit.offset_at(time));
+        (self.center_x + dx, self.center_y + dy)
+    }
+}
+
+/// A refuelling pad on a planet's surface that tops up the active stage while landed on it.
+#[derive(Clone)]
+pub struct FuelDepot {
+    pub planet: usize, // index into Level::planets
+    pub angle: f32,    // radians, position on the surface
+    pub rate: f32,     // fuel mass per second
+}
+
+#[derive(Clone)]
+pub struct Stage {
+    pub dry_mass: f32,
+    pub fuel_mass: f32,
+    pub thrust: f32,
+    pub isp: f32, // specific impulse in seconds
+}
+
 #[derive(Clone)]
 pub struct Rocket {
     pub x: f32,
@@ -54,8 +116,30 @@ pub struct Rocket {
     pub speed_y: f32,
     pub orientation: f32, // degrees, 0/360 = up
     pub landed: bool,
-    pub engine_on: bool,
-    pub fuel: f32, // seconds of engine burn remaining
+    pub throttle: f32, // 0.0 = engine off, 1.0 = full thrust
+    pub stages: Vec<Stage>, // in firing order, the first one is the active stage
+    pub heat: f32, // 0.0 = cold, 1.0 = burns up
+    pub angular_velocity: f32, // degrees per second, only used with RotationMode::Momentum
+    pub rcs_fuel: f32, // seconds of RCS thruster firing remaining
+    pub strafe_x: f32, // RCS translation command to the rocket's right, -1.0..=1.0
+    pub strafe_y: f32, // RCS translation command towards the nose, -1.0..=1.0
+}
+
+impl Rocket {
+    /// Fuel mass left in the active stage.
+    pub fn fuel(&self) -> f32 {
+        self.stages.first().map_or(0.0, |stage| stage.fuel_mass)
+    }
+}
+
+/// A spent stage drifting under gravity after being jettisoned.
+#[derive(Clone)]
+pub struct Debris {
+    pub x: f32,
+    pub y: f32,
+    pub speed_x: f32,
+    pub speed_y: f32,
+    pub orientation: f32,
 }
 
 #[derive(Clone)]
@@ -77,223 +161,891 @@ impl WinCondition {
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum RotationMode {
+    /// A/D turn the rocket at a fixed rate for free
+    Instant,
+    /// A/D fire RCS thrusters that change the angular velocity and use up RCS fuel
+    Momentum,
+}
+
+/// Directions the rocket can be turned to hold automatically.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Assist {
+    /// Along the velocity relative to the dominant body
+    Prograde,
+    /// Against the velocity relative to the dominant body
+    Retrograde,
+    /// Towards the center of the dominant body
+    RadialIn,
+    /// Away from the center of the dominant body
+    RadialOut,
+    /// Towards the middle of the goal circle
+    Goal,
+}
+
+impl Assist {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Assist::Prograde => "Prograde",
+            Assist::Retrograde => "Retrograde",
+            Assist::RadialIn => "Radial in",
+            Assist::RadialOut => "Radial out",
+            Assist::Goal => "Goal",
+        }
+    }
+}
+
+/// What happens when the rocket reaches the edge of the play area.
+#[derive(Clone)]
+pub enum Bounds {
+    /// No boundary, the rocket can fly off forever
+    Open,
+    /// Leaving one edge brings the rocket back in on the opposite edge
+    Wrap(Rect),
+    /// Walls that bounce the rocket back, keeping `restitution` of its speed
+    Wall { rect: Rect, restitution: f32 },
+    /// Leaving the rectangle fails the attempt
+    Lose(Rect),
+}
+
 #[derive(Clone)]
 pub struct Level {
     pub name: &'static str,
     pub planets: Vec<PlanetDef>,
     pub initial_rocket: Rocket,
     pub win_condition: WinCondition,
+    pub rotation: RotationMode,
+    pub canisters: Vec<FuelCanister>,
+    pub depots: Vec<FuelDepot>,
+    pub hazards: Vec<HazardDef>,
+    pub bounds: Bounds,
+    pub world: Vec2, // size of the level in world pixels, starting at the origin
+    pub star: Option<usize>, // planet that lights and shades the others
+    pub assists: bool,       // false for hard mode, without the auto-hold assists
+    pub seed: Option<u64>,   // what a generated level was made from, shown so it can be shared
+    pub par: Option<f32>,    // seconds the solver's run took, which the medals are measured against
 }
 
 impl Level {
     pub fn all() -> Vec<Self> {
+        // the size of the screen the single-screen levels were laid out on
+        let (width, height) = (1920.0, 1080.0);
         vec![
             Self {
                 name: "Level 1",
                 planets: vec![
                     PlanetDef {
-                        center_x: screen_width() / 2.0,
-                        center_y: screen_height() / 2.0,
+                        center_x: width / 2.0,
+                        center_y: height / 2.0,
                         radius: 30.0,
+                        density: 1.0,
                         image: 5,
+                        spin: 8.0,
                         orbit: None,
+                        atmosphere: None,
                     },
                     PlanetDef {
-                        center_x: screen_width() / 2.0,
-                        center_y: screen_height() / 2.0,
+                        center_x: width / 2.0,
+                        center_y: height / 2.0,
                         radius: 15.0,
+                        density: 1.0,
                         image: 3,
+                        spin: -12.0,
                         orbit: Some(Orbit {
                             radius: 150.0,
                             speed: 0.5,
                             initial_angle: 0.0,
                         }),
+                        atmosphere: None,
                     },
                 ],
                 initial_rocket: Rocket {
                     x: 100.0,
-                    y: screen_height() - 100.0,
+                    y: height - 100.0,
                     speed_x: 120.0,
                     speed_y: 0.0,
                     orientation: 90.0,
                     landed: false,
-                    engine_on: false,
-                    fuel: 20.0,
+                    throttle: 0.0,
+                    stages: vec![Stage { dry_mass: 0.5, fuel_mass: 0.5, thrust: 10.0, isp: 40.0 }],
+                    heat: 0.0,
+                    angular_velocity: 0.0,
+                    rcs_fuel: 0.0,
+                    strafe_x: 0.0,
+                    strafe_y: 0.0,
                 },
                 win_condition: WinCondition::CircleAnySpeed {
-                    x: screen_width() - 150.0,
+                    x: width - 150.0,
                     y: 150.0,
                     radius: 50.0,
                 },
+                rotation: RotationMode::Instant,
+                canisters: vec![],
+                depots: vec![],
+                hazards: vec![],
+                bounds: Bounds::Open,
+                world: vec2(width, height),
+                star: None,
+                assists: true,
+                seed: None,
+                par: Some(13.5),
             },
             Self {
                 name: "Level 2",
                 planets: vec![],
                 initial_rocket: Rocket {
-                    x: screen_width() / 2.0,
-                    y: screen_height() / 2.0,
+                    x: width / 2.0,
+                    y: height / 2.0,
                     speed_x: 0.0,
                     speed_y: 0.0,
                     orientation: 0.0,
                     landed: false,
-                    engine_on: false,
-                    fuel: 20.0,
+                    throttle: 0.0,
+                    stages: vec![Stage { dry_mass: 0.5, fuel_mass: 0.5, thrust: 10.0, isp: 40.0 }],
+                    heat: 0.0,
+                    angular_velocity: 0.0,
+                    rcs_fuel: 0.0,
+                    strafe_x: 0.0,
+                    strafe_y: 0.0,
                 },
                 win_condition: WinCondition::Circle {
-                    x: screen_width() - 150.0,
+                    x: width - 150.0,
                     y: 150.0,
                     radius: 50.0,
                     max_speed: 2.0,
                 },
+                rotation: RotationMode::Instant,
+                canisters: vec![],
+                depots: vec![],
+                hazards: vec![],
+                bounds: Bounds::Open,
+                world: vec2(width, height),
+                star: None,
+                assists: true,
+                seed: None,
+                par: Some(119.8),
+            },
+            // No planets and a rocket that keeps spinning until the thrusters stop it
+            Self {
+                name: "Spin Control",
+                planets: vec![],
+                initial_rocket: Rocket {
+                    x: 300.0,
+                    y: height / 2.0,
+                    speed_x: 0.0,
+                    speed_y: 0.0,
+                    orientation: 0.0,
+                    landed: false,
+                    throttle: 0.0,
+                    stages: vec![Stage { dry_mass: 0.5, fuel_mass: 0.5, thrust: 10.0, isp: 40.0 }],
+                    heat: 0.0,
+                    angular_velocity: 0.0,
+                    rcs_fuel: 10.0,
+                    strafe_x: 0.0,
+                    strafe_y: 0.0,
+                },
+                win_condition: WinCondition::Circle {
+                    x: width - 600.0,
+                    y: height / 2.0,
+                    radius: 60.0,
+                    max_speed: 40.0,
+                },
+                rotation: RotationMode::Momentum,
+                canisters: vec![],
+                depots: vec![],
+                hazards: vec![],
+                bounds: Bounds::Open,
+                world: vec2(width, height),
+                star: None,
+                assists: true,
+                seed: None,
+                par: Some(100.0),
             },
             // Two large planets guard a narrow gap; thread the needle to reach the goal
             Self {
                 name: "The Gauntlet",
                 planets: vec![
                     PlanetDef {
-                        center_x: screen_width() / 2.0,
-                        center_y: screen_height() / 2.0 - 120.0,
+                        center_x: width / 2.0,
+                        center_y: height / 2.0 - 120.0,
                         radius: 45.0,
+                        density: 1.0,
                         image: 7,
+                        spin: 10.0,
                         orbit: None,
+                        atmosphere: None,
                     },
                     PlanetDef {
-                        center_x: screen_width() / 2.0,
-                        center_y: screen_height() / 2.0 + 120.0,
+                        center_x: width / 2.0,
+                        center_y: height / 2.0 + 120.0,
                         radius: 45.0,
+                        density: 1.0,
                         image: 7,
+                        spin: 15.0,
                         orbit: None,
+                        atmosphere: None,
                     },
                     PlanetDef {
-                        center_x: screen_width() * 0.8,
-                        center_y: screen_height() / 2.0,
+                        center_x: width * 0.8,
+                        center_y: height / 2.0,
                         radius: 20.0,
+                        density: 1.0,
                         image: 2,
+                        spin: -6.0,
                         orbit: None,
+                        atmosphere: None,
                     },
                 ],
                 initial_rocket: Rocket {
                     x: 100.0,
-                    y: screen_height() / 2.0,
+                    y: height / 2.0,
                     speed_x: 150.0,
                     speed_y: 0.0,
                     orientation: 90.0,
                     landed: false,
-                    engine_on: false,
-                    fuel: 8.0,
+                    throttle: 0.0,
+                    stages: vec![Stage { dry_mass: 0.5, fuel_mass: 0.2, thrust: 10.0, isp: 40.0 }],
+                    heat: 0.0,
+                    angular_velocity: 0.0,
+                    rcs_fuel: 0.0,
+                    strafe_x: 0.0,
+                    strafe_y: 0.0,
                 },
                 win_condition: WinCondition::CircleAnySpeed {
-                    x: screen_width() - 100.0,
-                    y: screen_height() / 2.0,
+                    x: width - 100.0,
+                    y: height / 2.0,
                     radius: 60.0,
                 },
+                rotation: RotationMode::Instant,
+                canisters: vec![],
+                depots: vec![],
+                hazards: vec![],
+                bounds: Bounds::Open,
+                world: vec2(width, height),
+                star: None,
+                assists: true,
+                seed: None,
+                par: Some(5.7),
             },
             // Two planets locked in orbit around each other; navigate the chaos
             Self {
                 name: "Binary Stars",
                 planets: vec![
                     PlanetDef {
-                        center_x: screen_width() / 2.0,
-                        center_y: screen_height() / 2.0,
+                        center_x: width / 2.0,
+                        center_y: height / 2.0,
                         radius: 35.0,
+                        density: 1.0,
                         image: 1,
+                        spin: 20.0,
                         orbit: Some(Orbit {
                             radius: 120.0,
                             speed: 0.8,
                             initial_angle: 0.0,
                         }),
+                        atmosphere: None,
                     },
                     PlanetDef {
-                        center_x: screen_width() / 2.0,
-                        center_y: screen_height() / 2.0,
+                        center_x: width / 2.0,
+                        center_y: height / 2.0,
                         radius: 35.0,
+                        density: 1.0,
                         image: 9,
+                        spin: 5.0,
                         orbit: Some(Orbit {
                             radius: 120.0,
                             speed: 0.8,
                             initial_angle: std::f32::consts::PI,
                         }),
+                        atmosphere: None,
                     },
                 ],
                 initial_rocket: Rocket {
-                    x: screen_width() / 2.0,
+                    x: width / 2.0,
                     y: 80.0,
                     speed_x: 0.0,
                     speed_y: 0.0,
                     orientation: 180.0,
                     landed: false,
-                    engine_on: false,
-                    fuel: 15.0,
+                    throttle: 0.0,
+                    stages: vec![Stage { dry_mass: 0.5, fuel_mass: 0.375, thrust: 10.0, isp: 40.0 }],
+                    heat: 0.0,
+                    angular_velocity: 0.0,
+                    rcs_fuel: 0.0,
+                    strafe_x: 0.0,
+                    strafe_y: 0.0,
                 },
                 win_condition: WinCondition::Circle {
-                    x: screen_width() / 2.0,
-                    y: screen_height() - 80.0,
+                    x: width / 2.0,
+                    y: height - 80.0,
                     radius: 60.0,
                     max_speed: 50.0,
                 },
+                rotation: RotationMode::Instant,
+                canisters: vec![],
+                depots: vec![],
+                hazards: vec![],
+                bounds: Bounds::Open,
+                world: vec2(width, height),
+                star: None,
+                assists: true,
+                seed: None,
+                par: Some(10.7),
+            },
+            // Walls that bounce the rocket back; bank off them around the planet
+            Self {
+                name: "Pinball",
+                planets: vec![
+                    PlanetDef {
+                        center_x: width / 2.0,
+                        center_y: height / 2.0,
+                        radius: 60.0,
+                        density: 1.0,
+                        image: 4,
+                        spin: -10.0,
+                        orbit: None,
+                        atmosphere: None,
+                    },
+                ],
+                initial_rocket: Rocket {
+                    x: 200.0,
+                    y: 200.0,
+                    speed_x: -150.0,
+                    speed_y: 120.0,
+                    orientation: 180.0,
+                    landed: false,
+                    throttle: 0.0,
+                    stages: vec![Stage { dry_mass: 0.5, fuel_mass: 0.15, thrust: 10.0, isp: 40.0 }],
+                    heat: 0.0,
+                    angular_velocity: 0.0,
+                    rcs_fuel: 0.0,
+                    strafe_x: 0.0,
+                    strafe_y: 0.0,
+                },
+                win_condition: WinCondition::CircleAnySpeed {
+                    x: width - 150.0,
+                    y: 150.0,
+                    radius: 60.0,
+                },
+                rotation: RotationMode::Instant,
+                canisters: vec![],
+                depots: vec![],
+                hazards: vec![],
+                bounds: Bounds::Wall {
+                    rect: Rect::new(0.0, 0.0, width, height),
+                    restitution: 0.7,
+                },
+                world: vec2(width, height),
+                star: None,
+                assists: true,
+                seed: None,
+                par: Some(27.1),
+            },
+            // The goal is behind the rocket, but flying off one edge comes back in on the other
+            Self {
+                name: "Wraparound",
+                planets: vec![
+                    PlanetDef {
+                        center_x: width / 2.0,
+                        center_y: height / 2.0,
+                        radius: 30.0,
+                        density: 1.0,
+                        image: 6,
+                        spin: 12.0,
+                        orbit: None,
+                        atmosphere: None,
+                    },
+                ],
+                initial_rocket: Rocket {
+                    x: width - 300.0,
+                    y: height / 2.0,
+                    speed_x: -60.0,
+                    speed_y: 0.0,
+                    orientation: 270.0,
+                    landed: false,
+                    throttle: 0.0,
+                    stages: vec![Stage { dry_mass: 0.5, fuel_mass: 0.1, thrust: 10.0, isp: 40.0 }],
+                    heat: 0.0,
+                    angular_velocity: 0.0,
+                    rcs_fuel: 0.0,
+                    strafe_x: 0.0,
+                    strafe_y: 0.0,
+                },
+                win_condition: WinCondition::CircleAnySpeed {
+                    x: 400.0,
+                    y: height / 2.0,
+                    radius: 60.0,
+                },
+                rotation: RotationMode::Instant,
+                canisters: vec![],
+                depots: vec![],
+                hazards: vec![],
+                bounds: Bounds::Wrap(Rect::new(0.0, 0.0, width, height)),
+                world: vec2(width, height),
+                star: None,
+                assists: true,
+                seed: None,
+                par: Some(26.6),
             },
             // One massive planet with a fast moon; use gravity to slingshot with minimal fuel
             Self {
                 name: "Slingshot",
                 planets: vec![
                     PlanetDef {
-                        center_x: screen_width() * 0.35,
-                        center_y: screen_height() / 2.0,
+                        center_x: width * 0.35,
+                        center_y: height / 2.0,
                         radius: 50.0,
+                        density: 1.0,
                         image: 0,
+                        spin: -9.0,
                         orbit: None,
+                        atmosphere: None,
                     },
                     PlanetDef {
-                        center_x: screen_width() * 0.35,
-                        center_y: screen_height() / 2.0,
+                        center_x: width * 0.35,
+                        center_y: height / 2.0,
                         radius: 12.0,
+                        density: 1.0,
                         image: 4,
+                        spin: 12.0,
                         orbit: Some(Orbit {
                             radius: 180.0,
                             speed: -1.2,
                             initial_angle: 0.0,
                         }),
+                        atmosphere: None,
                     },
                 ],
                 initial_rocket: Rocket {
-                    x: screen_width() - 120.0,
+                    x: width - 120.0,
                     y: 100.0,
                     speed_x: -80.0,
                     speed_y: 30.0,
                     orientation: 270.0,
                     landed: false,
-                    engine_on: false,
-                    fuel: 4.0,
+                    throttle: 0.0,
+                    stages: vec![Stage { dry_mass: 0.5, fuel_mass: 0.1, thrust: 10.0, isp: 40.0 }],
+                    heat: 0.0,
+                    angular_velocity: 0.0,
+                    rcs_fuel: 0.0,
+                    strafe_x: 0.0,
+                    strafe_y: 0.0,
                 },
                 win_condition: WinCondition::CircleAnySpeed {
                     x: 120.0,
-                    y: screen_height() - 120.0,
+                    y: height - 120.0,
                     radius: 70.0,
                 },
+                rotation: RotationMode::Instant,
+                canisters: vec![],
+                depots: vec![],
+                hazards: vec![],
+                bounds: Bounds::Open,
+                world: vec2(width, height),
+                star: None,
+                assists: true,
+                seed: None,
+                par: Some(96.6),
+            },
+            // A planet with a thick atmosphere; skim it to shed speed without burning up
+            Self {
+                name: "Aerobrake",
+                planets: vec![
+                    PlanetDef {
+                        center_x: width / 2.0,
+                        center_y: height / 2.0,
+                        radius: 40.0,
+                        density: 1.0,
+                        image: 6,
+                        spin: -15.0,
+                        orbit: None,
+                        atmosphere: Some(Atmosphere {
+                            thickness: 60.0,
+                            density: 0.004,
+                            scale_height: 20.0,
+                        }),
+                    },
+                ],
+                initial_rocket: Rocket {
+                    x: 100.0,
+                    y: height / 2.0 - 200.0,
+                    speed_x: 220.0,
+                    speed_y: 0.0,
+                    orientation: 90.0,
+                    landed: false,
+                    throttle: 0.0,
+                    stages: vec![Stage { dry_mass: 0.5, fuel_mass: 0.075, thrust: 10.0, isp: 40.0 }],
+                    heat: 0.0,
+                    angular_velocity: 0.0,
+                    rcs_fuel: 0.0,
+                    strafe_x: 0.0,
+                    strafe_y: 0.0,
+                },
+                win_condition: WinCondition::Circle {
+                    x: width / 2.0,
+                    y: height - 100.0,
+                    radius: 80.0,
+                    max_speed: 180.0,
+                },
+                rotation: RotationMode::Instant,
+                canisters: vec![],
+                depots: vec![],
+                hazards: vec![],
+                bounds: Bounds::Open,
+                world: vec2(width, height),
+                star: None,
+                assists: true,
+                seed: None,
+                par: Some(5.7),
+            },
+            // A heavy booster and a frugal upper stage; drop the booster once it runs dry
+            Self {
+                name: "Staging",
+                planets: vec![
+                    PlanetDef {
+                        center_x: width / 2.0,
+                        center_y: height / 2.0,
+                        radius: 25.0,
+                        density: 1.0,
+                        image: 8,
+                        spin: 7.0,
+                        orbit: None,
+                        atmosphere: None,
+                    },
+                ],
+                initial_rocket: Rocket {
+                    x: 120.0,
+                    y: height - 120.0,
+                    speed_x: 0.0,
+                    speed_y: 0.0,
+                    orientation: 45.0,
+                    landed: false,
+                    throttle: 0.0,
+                    stages: vec![
+                        Stage { dry_mass: 0.6, fuel_mass: 1.2, thrust: 30.0, isp: 25.0 },
+                        Stage { dry_mass: 0.3, fuel_mass: 0.4, thrust: 6.0, isp: 45.0 },
+                    ],
+                    heat: 0.0,
+                    angular_velocity: 0.0,
+                    rcs_fuel: 0.0,
+                    strafe_x: 0.0,
+                    strafe_y: 0.0,
+                },
+                win_condition: WinCondition::Circle {
+                    x: width - 150.0,
+                    y: 150.0,
+                    radius: 60.0,
+                    max_speed: 60.0,
+                },
+                rotation: RotationMode::Instant,
+                canisters: vec![],
+                depots: vec![],
+                hazards: vec![],
+                bounds: Bounds::Open,
+                world: vec2(width, height),
+                star: None,
+                assists: true,
+                seed: None,
+                par: Some(42.6),
+            },
+            // Not enough fuel to reach the goal; grab canisters or land on the depot moon to top up
+            Self {
+                name: "Refuel Run",
+                planets: vec![
+                    PlanetDef {
+                        center_x: width / 2.0,
+                        center_y: height / 2.0,
+                        radius: 35.0,
+                        density: 1.0,
+                        image: 5,
+                        spin: 10.0,
+                        orbit: None,
+                        atmosphere: None,
+                    },
+                    // a light moon with barely any gravity, so the rocket can take off again
+                    PlanetDef {
+                        center_x: width * 0.25,
+                        center_y: height * 0.3,
+                        radius: 20.0,
+                        density: 0.003,
+                        image: 3,
+                        spin: -8.0,
+                        orbit: None,
+                        atmosphere: None,
+                    },
+                ],
+                initial_rocket: Rocket {
+                    x: 100.0,
+                    y: height - 100.0,
+                    speed_x: 40.0,
+                    speed_y: -40.0,
+                    orientation: 45.0,
+                    landed: false,
+                    throttle: 0.0,
+                    stages: vec![Stage { dry_mass: 0.5, fuel_mass: 0.05, thrust: 10.0, isp: 40.0 }],
+                    heat: 0.0,
+                    angular_velocity: 0.0,
+                    rcs_fuel: 0.0,
+                    strafe_x: 0.0,
+                    strafe_y: 0.0,
+                },
+                win_condition: WinCondition::Circle {
+                    x: width - 150.0,
+                    y: 150.0,
+                    radius: 60.0,
+                    max_speed: 80.0,
+                },
+                rotation: RotationMode::Instant,
+                canisters: vec![
+                    FuelCanister {
+                        center_x: width * 0.6,
+                        center_y: height * 0.2,
+                        orbit: None,
+                        fuel_mass: 0.1,
+                    },
+                    FuelCanister {
+                        center_x: width / 2.0,
+                        center_y: height / 2.0,
+                        orbit: Some(Orbit {
+                            radius: 140.0,
+                            speed: 0.6,
+                            initial_angle: std::f32::consts::PI,
+                        }),
+                        fuel_mass: 0.1,
+                    },
+                ],
+                depots: vec![
+                    FuelDepot {
+                        planet: 1,
+                        angle: -std::f32::consts::FRAC_PI_2,
+                        rate: 0.05,
+                    },
+                ],
+                hazards: vec![],
+                bounds: Bounds::Open,
+                world: vec2(width, height),
+                star: None,
+                assists: true,
+                seed: None,
+                par: Some(89.6),
+            },
+            // Cross a rotating asteroid belt while stray rocks drift and tumble through the system
+            Self {
+                name: "Asteroid Belt",
+                planets: vec![
+                    PlanetDef {
+                        center_x: width / 2.0,
+                        center_y: height / 2.0,
+                        radius: 40.0,
+                        density: 1.0,
+                        image: 1,
+                        spin: 6.0,
+                        orbit: None,
+                        atmosphere: None,
+                    },
+                ],
+                initial_rocket: Rocket {
+                    x: 120.0,
+                    y: 120.0,
+                    speed_x: 60.0,
+                    speed_y: 20.0,
+                    orientation: 110.0,
+                    landed: false,
+                    throttle: 0.0,
+                    stages: vec![Stage { dry_mass: 0.5, fuel_mass: 0.3, thrust: 10.0, isp: 40.0 }],
+                    heat: 0.0,
+                    angular_velocity: 0.0,
+                    rcs_fuel: 0.0,
+                    strafe_x: 0.0,
+                    strafe_y: 0.0,
+                },
+                win_condition: WinCondition::CircleAnySpeed {
+                    x: width / 2.0 + 120.0,
+                    y: height / 2.0 + 60.0,
+                    radius: 50.0,
+                },
+                rotation: RotationMode::Instant,
+                canisters: vec![],
+                depots: vec![],
+                hazards: vec![
+                    HazardDef::Belt {
+                        center_x: width / 2.0,
+                        center_y: height / 2.0,
+                        inner_radius: 220.0,
+                        outer_radius: 320.0,
+                        count: 300,
+                        speed: 0.15,
+                        seed: 2024,
+                    },
+                    HazardDef::Drift {
+                        x: width - 100.0,
+                        y: 100.0,
+                        speed_x: -40.0,
+                        speed_y: 25.0,
+                        radius: 10.0,
+                    },
+                    HazardDef::Tumble {
+                        x: width / 2.0 - 150.0,
+                        y: height / 2.0,
+                        speed_x: 60.0,
+                        speed_y: 0.0,
+                        radius: 8.0,
+                    },
+                ],
+                bounds: Bounds::Open,
+                world: vec2(width, height),
+                star: None,
+                assists: true,
+                seed: None,
+                par: Some(7.0),
+            },
+            // A whole solar system, several screens across; transfer out to the far orbit
+            Self {
+                name: "Solar System",
+                planets: vec![
+                    PlanetDef {
+                        center_x: 3000.0,
+                        center_y: 2000.0,
+                        radius: 120.0,
+                        density: 0.05,
+                        image: 7,
+                        spin: 14.0,
+                        orbit: None,
+                        atmosphere: None,
+                    },
+                    PlanetDef {
+                        center_x: 3000.0,
+                        center_y: 2000.0,
+                        radius: 30.0,
+                        density: 0.1,
+                        image: 2,
+                        spin: -10.0,
+                        orbit: Some(Orbit {
+                            radius: 600.0,
+                            speed: 0.15,
+                            initial_angle: 2.5,
+                        }),
+                        atmosphere: None,
+                    },
+                    PlanetDef {
+                        center_x: 3000.0,
+                        center_y: 2000.0,
+                        radius: 40.0,
+                        density: 0.1,
+                        image: 5,
+                        spin: 9.0,
+                        orbit: Some(Orbit {
+                            radius: 1400.0,
+                            speed: 0.07,
+                            initial_angle: 4.0,
+                        }),
+                        atmosphere: None,
+                    },
+                ],
+                initial_rocket: Rocket {
+                    x: 3000.0,
+                    y: 1000.0,
+                    speed_x: 113.0,
+                    speed_y: 0.0,
+                    orientation: 90.0,
+                    landed: false,
+                    throttle: 0.0,
+                    stages: vec![Stage { dry_mass: 0.5, fuel_mass: 0.2, thrust: 10.0, isp: 40.0 }],
+                    heat: 0.0,
+                    angular_velocity: 0.0,
+                    rcs_fuel: 0.0,
+                    strafe_x: 0.0,
+                    strafe_y: 0.0,
+                },
+                win_condition: WinCondition::CircleAnySpeed {
+                    x: 4800.0,
+                    y: 2000.0,
+                    radius: 150.0,
+                },
+                rotation: RotationMode::Instant,
+                canisters: vec![],
+                depots: vec![],
+                hazards: vec![],
+                bounds: Bounds::Lose(Rect::new(0.0, 0.0, 6000.0, 4000.0)),
+                world: vec2(6000.0, 4000.0),
+                star: Some(0),
+                assists: true,
+                seed: None,
+                par: Some(67.2),
             },
         ]
     }
 }
 
+/// How many failed attempts keep their trail on screen.
+const MAX_PAST_TRAILS: usize = 3;
+
+/// A spot the rocket passed through, for drawing where it has been.
+#[derive(Clone)]
+pub struct TrailPoint {
+    pub x: f32,
+    pub y: f32,
+    pub speed: f32,
+    pub burning: bool,
+    pub time: f64,
+}
+
 #[derive(Clone)]
 pub struct LevelState {
     pub level: Level,
     pub rocket: Rocket,
-    pub start_time: f64,
+    pub debris: Vec<Debris>,
+    pub collected: Vec<bool>, // per canister, whether it was picked up this attempt
+    pub asteroids: Vec<Asteroid>,
+    pub time: f64,    // simulated seconds since the level started
+    pub started: f64, // `time` when the current attempt began
+    pub trail: Vec<TrailPoint>,
+    pub past_trails: Vec<Vec<TrailPoint>>, // oldest attempt first
+    pub grid: SpatialGrid,                 // broad phase over `asteroids`, kept up to date as they move
 }
 
 impl LevelState {
     pub fn new(design: Level) -> Self {
         let rocket = design.initial_rocket.clone();
-        Self { level: design, rocket, start_time: get_time() }
+        let collected = vec![false; design.canisters.len()];
+        let asteroids = hazards::spawn(&design.hazards);
+        let grid = SpatialGrid::build(&asteroids, hazards::CELL_SIZE);
+        Self {
+            level: design,
+            rocket,
+            debris: Vec::new(),
+            collected,
+            asteroids,
+            time: 0.0,
+            started: 0.0,
+            trail: Vec::new(),
+            past_trails: Vec::new(),
+            grid,
+        }
     }
 
     pub fn reset_rocket(&mut self) {
+        let trail = std::mem::take(&mut self.trail);
+        if !trail.is_empty() {
+            self.past_trails.push(trail);
+            if self.past_trails.len() > MAX_PAST_TRAILS {
+                self.past_trails.remove(0);
+            }
+        }
         self.rocket = self.level.initial_rocket.clone();
+        self.debris.clear();
+        self.collected.fill(false);
+        self.started = self.time;
     }
 
     pub fn elapsed(&self) -> f64 {
-        get_time() - self.start_time
+        self.time
+    }
+
+    /// Seconds the current attempt has been flying, what a win is timed by.
+    pub fn attempt_time(&self) -> f32 {
+        (self.time - self.started) as f32
     }
 
     pub fn current_planets(&self) -> Vec<Planet> {
@@ -302,19 +1054,97 @@ impl LevelState {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CrashCause {
+    Collision,
+    Overheat,
+    OutOfBounds,
+}
+
+impl CrashCause {
+    pub fn description(&self) -> &'static str {
+        match self {
+            CrashCause::Collision => "Crashed",
+            CrashCause::Overheat => "Burned up",
+            CrashCause::OutOfBounds => "Lost in space",
+        }
+    }
+}
+
+/// A crash that is still playing out in slow motion.
+#[derive(Clone)]
+pub struct Crash {
+    pub x: f32,
+    pub y: f32,
+    pub cause: CrashCause,
+    pub timer: f32, // real seconds since the crash
+}
+
 #[derive(Clone)]
 pub struct GameState {
     pub level: LevelState,
     pub show_hud: bool,
     pub show_path: bool,
+    pub show_minimap: bool,
+    pub show_orbits: bool,
+    pub show_field: bool, // gravity vector field overlay
+    pub show_past_trails: bool,
+    pub path_horizon: f32, // seconds of trajectory prediction
+    pub burn_plan: Option<f32>, // seconds of burn assumed by the burn prediction, None to burn until empty
+    pub sas: bool,
+    pub assist: Option<Assist>, // direction being held automatically
+    pub camera: Camera,
+    pub particles: Particles,
+    pub crash: Option<Crash>,
+    pub last_crash: Option<Vec2>, // where the previous attempt ended, marked on the next one
+    pub skin: Skin,
+    pub solver: Option<Solver>, // search behind the solution hint
+    pub autopilot: bool,        // fly the solver's solution once it's found
+    pub autopilot_lag: f32,     // frame time the autopilot hasn't flown yet, less than a solver step
+    pub replay: Option<Replay>, // recording of a daily challenge, which gets a single attempt
 }
 
 impl GameState {
-    pub fn new(level: Level) -> Self {
+    pub fn new(level: Level, skin: Skin) -> Self {
+        Self::from_state(LevelState::new(level), skin, vec2(screen_width(), screen_height()))
+    }
+
+    /// Picks up from an existing level state. The solver uses this without a window,
+    /// so the screen size is passed in.
+    pub fn from_state(level: LevelState, skin: Skin, screen: Vec2) -> Self {
+        let camera = Camera::new(level.level.world, screen);
         Self {
-            level: LevelState::new(level),
+            level,
             show_hud: true,
             show_path: true,
+            show_minimap: true,
+            show_orbits: false,
+            show_field: false,
+            show_past_trails: true,
+            path_horizon: 5.0,
+            burn_plan: None,
+            sas: false,
+            assist: None,
+            camera,
+            particles: Particles::new(),
+            crash: None,
+            last_crash: None,
+            skin,
+            solver: None,
+            autopilot: false,
+            autopilot_lag: 0.0,
+            replay: None,
+        }
+    }
+
+    /// Starts a new attempt, remembering where the last one crashed.
+    pub fn restart(&mut self) {
+        if let Some(crash) = self.crash.take() {
+            self.last_crash = Some(vec2(crash.x, crash.y));
         }
+        self.level.reset_rocket();
+        self.solver = None;
+        self.autopilot = false;
+        self.autopilot_lag = 0.0;
     }
 }